@@ -0,0 +1,145 @@
+//! Named values shared between the game and a behaviour tree.
+
+use std::collections::BTreeMap;
+use std::hash::Hasher;
+use sfml::system::Vector2f;
+use checksum::{SimState, StateHasher};
+use world::Handle;
+
+/// A value on a ``Blackboard``.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Number(f32),
+    Vector(Vector2f),
+    Entity(Handle),
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Value {
+        Value::Bool(value)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Value {
+        Value::Number(value)
+    }
+}
+
+impl From<Vector2f> for Value {
+    fn from(value: Vector2f) -> Value {
+        Value::Vector(value)
+    }
+}
+
+impl From<Handle> for Value {
+    fn from(value: Handle) -> Value {
+        Value::Entity(value)
+    }
+}
+
+/// Memory for one behaviour tree. The game writes what the agent knows, such
+/// as where its target is, and leaves read and write it by name.
+/// The typed getters return ``None`` for missing keys and for values of another type.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Blackboard {
+    // Ordered so ``iter`` and checksums go the same way every run.
+    values: BTreeMap<String, Value>,
+}
+
+impl Blackboard {
+    /// Create an empty blackboard.
+    pub fn new() -> Blackboard {
+        Default::default()
+    }
+
+    /// Set a value, returning the one it replaced.
+    pub fn set<V: Into<Value>>(&mut self, key: &str, value: V) -> Option<Value> {
+        self.values.insert(key.to_owned(), value.into())
+    }
+
+    /// Return a value.
+    pub fn get(&self, key: &str) -> Option<Value> {
+        self.values.get(key).cloned()
+    }
+
+    /// Remove a value, returning it.
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        self.values.remove(key)
+    }
+
+    /// Test whether a value is set.
+    pub fn contains(&self, key: &str) -> bool {
+        self.values.contains_key(key)
+    }
+
+    /// Remove every value.
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    /// Return a flag.
+    pub fn bool(&self, key: &str) -> Option<bool> {
+        match self.get(key) {
+            Some(Value::Bool(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Return a number.
+    pub fn number(&self, key: &str) -> Option<f32> {
+        match self.get(key) {
+            Some(Value::Number(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Return a vector.
+    pub fn vector(&self, key: &str) -> Option<Vector2f> {
+        match self.get(key) {
+            Some(Value::Vector(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Return an entity.
+    pub fn entity(&self, key: &str) -> Option<Handle> {
+        match self.get(key) {
+            Some(Value::Entity(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Iterate over every value along with its key, in order of the key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Value)> {
+        self.values.iter().map(|(key, &value)| (key.as_str(), value))
+    }
+}
+
+impl SimState for Blackboard {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.values.len());
+        for (key, value) in self.iter() {
+            hasher.write_str(key);
+            match value {
+                Value::Bool(value) => {
+                    hasher.write_u8(0);
+                    hasher.write_bool(value);
+                },
+                Value::Number(value) => {
+                    hasher.write_u8(1);
+                    hasher.write_f32(value);
+                },
+                Value::Vector(value) => {
+                    hasher.write_u8(2);
+                    hasher.write_vector(value);
+                },
+                Value::Entity(value) => {
+                    hasher.write_u8(3);
+                    value.hash_state(hasher);
+                },
+            }
+        }
+    }
+}
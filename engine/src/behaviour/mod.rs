@@ -0,0 +1,325 @@
+//! Behaviour trees, for deciding what NPC ships do: patrol, trade between
+//! planets, attack, run away. Steering decides how to fly; a tree decides where.
+//!
+//! A ``BehaviourTree`` is ticked once per simulation tick, ``TICKS_SEC`` times a
+//! second, with the same ``dt`` as physics. Its leaves are named conditions and
+//! actions that the game implements on an ``Agent``, such as "is the player
+//! within 300 units" or "fly to planet 1". Each tree has a ``Blackboard`` for
+//! what its agent knows and remembers between ticks.
+//!
+//! # Nodes
+//!
+//! * ``selector`` tries its children in order until one doesn't fail. It starts
+//!   from the first child every tick, so a higher priority branch interrupts a
+//!   running lower one, which is aborted.
+//! * ``sequence`` runs its children in order until one fails. It remembers how
+//!   far it got, so children that already succeeded aren't run again until the
+//!   sequence finishes or fails.
+//! * ``invert`` swaps the success and failure of its one child.
+//! * ``succeed`` succeeds whenever its one child finishes, even by failing.
+//! * ``guard name args...`` runs its one child only while a condition holds,
+//!   checking it every tick and aborting the child when it stops holding.
+//! * ``wait seconds`` keeps running for a while, then succeeds.
+//! * ``condition name args...`` succeeds if a condition holds and fails otherwise.
+//! * ``action name args...`` runs an action, which succeeds, fails or keeps running.
+//!
+//! # Text format
+//!
+//! Trees can be written in files, one node per line, with children indented
+//! under their parent using spaces. Arguments are numbers or names, separated
+//! by whitespace. Blank lines and ``#`` comments are skipped.
+//!
+//! ```text
+//! selector
+//!     guard near player 300
+//!         action attack player
+//!     sequence
+//!         action fly_to 0
+//!         wait 2
+//!         action fly_to 1
+//! ```
+
+pub use self::blackboard::{Blackboard, Value};
+
+pub mod blackboard;
+mod parse;
+
+use std::fs;
+use std::hash::Hasher;
+use checksum::{SimState, StateHasher};
+
+/// How a node did on a tick.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Failure,
+    Running,
+}
+
+/// An argument to a leaf, written after its name.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Arg {
+    Number(f32),
+    Name(String),
+}
+
+impl Arg {
+    /// Return the argument if it is a number.
+    pub fn number(&self) -> Option<f32> {
+        match *self {
+            Arg::Number(value) => Some(value),
+            Arg::Name(_) => None,
+        }
+    }
+
+    /// Return the argument if it is a name.
+    pub fn name(&self) -> Option<&str> {
+        match *self {
+            Arg::Number(_) => None,
+            Arg::Name(ref name) => Some(name),
+        }
+    }
+}
+
+/// Something a behaviour tree controls, usually a small struct borrowing an
+/// NPC's physics and whatever it needs to look at.
+pub trait Agent {
+    /// Test a named condition. Return ``false`` for names you don't know.
+    fn condition(&self, name: &str, args: &[Arg], blackboard: &Blackboard) -> bool;
+
+    /// Run a named action for one tick of ``dt`` seconds. Return ``Running``
+    /// to be called again next tick, and ``Failure`` for names you don't know.
+    fn action(&mut self, name: &str, args: &[Arg], blackboard: &mut Blackboard, dt: f32)
+              -> Status;
+
+    /// Stop a running action that was interrupted before it finished.
+    /// Does nothing by default.
+    fn abort(&mut self, _name: &str, _args: &[Arg], _blackboard: &mut Blackboard) {}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Kind {
+    Sequence,
+    Selector,
+    Invert,
+    Succeed,
+    Guard(String, Vec<Arg>),
+    Wait(f32),
+    Condition(String, Vec<Arg>),
+    Action(String, Vec<Arg>),
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    kind: Kind,
+    children: Vec<usize>,
+    // The line it was read from, for error messages.
+    line: usize,
+    // Whether it returned ``Running`` the last time it was ticked.
+    running: bool,
+    // The child a sequence is up to.
+    current: usize,
+    // Seconds a wait has waited.
+    elapsed: f32,
+}
+
+impl Node {
+    fn new(kind: Kind, line: usize) -> Node {
+        Node {
+            kind,
+            children: Vec::new(),
+            line,
+            running: false,
+            current: 0,
+            elapsed: 0.,
+        }
+    }
+}
+
+/// A behaviour tree and its blackboard. See the module documentation.
+/// Clone a loaded tree to give several NPCs the same behaviour.
+#[derive(Clone, Debug)]
+pub struct BehaviourTree {
+    // In the order they were written, so the root comes first.
+    nodes: Vec<Node>,
+    blackboard: Blackboard,
+}
+
+impl BehaviourTree {
+    /// Read a tree from a file in the text format.
+    pub fn from_file(path: &str) -> Result<BehaviourTree, String> {
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        source.parse().map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Check that every condition and action in the tree is one of the given
+    /// names, to catch typos when loading rather than when the branch runs.
+    pub fn validate(&self, conditions: &[&str], actions: &[&str]) -> Result<(), String> {
+        for node in &self.nodes {
+            match node.kind {
+                Kind::Guard(ref name, _) | Kind::Condition(ref name, _)
+                        if !conditions.contains(&name.as_str()) => {
+                    return Err(format!("line {}: unknown condition: {}", node.line, name));
+                },
+                Kind::Action(ref name, _) if !actions.contains(&name.as_str()) => {
+                    return Err(format!("line {}: unknown action: {}", node.line, name));
+                },
+                _ => {},
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run the tree for one tick of ``dt`` seconds, returning how the root did.
+    /// Once the root finishes, the next tick starts over from the top.
+    pub fn tick<A: Agent + ?Sized>(&mut self, agent: &mut A, dt: f32) -> Status {
+        self.tick_node(0, agent, dt)
+    }
+
+    /// Abort whatever is running and start over from the top on the next tick.
+    /// The blackboard is kept.
+    pub fn reset<A: Agent + ?Sized>(&mut self, agent: &mut A) {
+        self.abort(0, agent);
+    }
+
+    /// Test whether anything was left running by the last tick.
+    pub fn is_running(&self) -> bool {
+        self.nodes[0].running
+    }
+
+    /// Access the blackboard.
+    pub fn blackboard(&self) -> &Blackboard {
+        &self.blackboard
+    }
+
+    /// Mutably access the blackboard.
+    pub fn blackboard_mut(&mut self) -> &mut Blackboard {
+        &mut self.blackboard
+    }
+
+    fn tick_node<A: Agent + ?Sized>(&mut self, index: usize, agent: &mut A, dt: f32) -> Status {
+        let status = match self.nodes[index].kind {
+            Kind::Sequence => self.tick_sequence(index, agent, dt),
+            Kind::Selector => self.tick_selector(index, agent, dt),
+            Kind::Invert => match self.tick_child(index, agent, dt) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                Status::Running => Status::Running,
+            },
+            Kind::Succeed => match self.tick_child(index, agent, dt) {
+                Status::Running => Status::Running,
+                _ => Status::Success,
+            },
+            Kind::Guard(ref name, ref args) => {
+                if agent.condition(name, args, &self.blackboard) {
+                    self.tick_child(index, agent, dt)
+                } else {
+                    let child = self.nodes[index].children[0];
+                    self.abort(child, agent);
+                    Status::Failure
+                }
+            },
+            Kind::Wait(seconds) => {
+                let node = &mut self.nodes[index];
+                node.elapsed += dt;
+                if node.elapsed >= seconds {
+                    node.elapsed = 0.;
+                    Status::Success
+                } else {
+                    Status::Running
+                }
+            },
+            Kind::Condition(ref name, ref args) => {
+                if agent.condition(name, args, &self.blackboard) {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            },
+            Kind::Action(ref name, ref args) => agent.action(name, args, &mut self.blackboard, dt),
+        };
+
+        self.nodes[index].running = status == Status::Running;
+        status
+    }
+
+    // Tick the only child of a decorator.
+    fn tick_child<A: Agent + ?Sized>(&mut self, index: usize, agent: &mut A, dt: f32) -> Status {
+        let child = self.nodes[index].children[0];
+        self.tick_node(child, agent, dt)
+    }
+
+    fn tick_sequence<A: Agent + ?Sized>(&mut self, index: usize, agent: &mut A, dt: f32)
+                                        -> Status {
+        while self.nodes[index].current < self.nodes[index].children.len() {
+            let child = self.nodes[index].children[self.nodes[index].current];
+            match self.tick_node(child, agent, dt) {
+                Status::Success => self.nodes[index].current += 1,
+                Status::Failure => {
+                    self.nodes[index].current = 0;
+                    return Status::Failure;
+                },
+                Status::Running => return Status::Running,
+            }
+        }
+
+        self.nodes[index].current = 0;
+        Status::Success
+    }
+
+    fn tick_selector<A: Agent + ?Sized>(&mut self, index: usize, agent: &mut A, dt: f32)
+                                        -> Status {
+        let count = self.nodes[index].children.len();
+        for i in 0..count {
+            let child = self.nodes[index].children[i];
+            let status = self.tick_node(child, agent, dt);
+            if status != Status::Failure {
+                // Anything lower down that was running has been interrupted.
+                for j in i + 1..count {
+                    let child = self.nodes[index].children[j];
+                    self.abort(child, agent);
+                }
+                return status;
+            }
+        }
+
+        Status::Failure
+    }
+
+    // Stop a subtree if it's running, telling the agent about interrupted actions.
+    fn abort<A: Agent + ?Sized>(&mut self, index: usize, agent: &mut A) {
+        if !self.nodes[index].running {
+            return;
+        }
+
+        {
+            let node = &mut self.nodes[index];
+            node.running = false;
+            node.current = 0;
+            node.elapsed = 0.;
+        }
+
+        if let Kind::Action(ref name, ref args) = self.nodes[index].kind {
+            agent.abort(name, args, &mut self.blackboard);
+        }
+
+        for i in 0..self.nodes[index].children.len() {
+            let child = self.nodes[index].children[i];
+            self.abort(child, agent);
+        }
+    }
+}
+
+impl SimState for BehaviourTree {
+    /// Hashes how far each node has got and the blackboard, but not the
+    /// shape of the tree, which doesn't change once it's read.
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        for node in &self.nodes {
+            hasher.write_bool(node.running);
+            hasher.write_usize(node.current);
+            hasher.write_f32(node.elapsed);
+        }
+        self.blackboard.hash_state(hasher);
+    }
+}
@@ -0,0 +1,232 @@
+//! Reading behaviour trees from text.
+
+use std::str::FromStr;
+use super::{Arg, BehaviourTree, Blackboard, Kind, Node};
+
+impl FromStr for BehaviourTree {
+    type Err = String;
+
+    /// Read a tree in the text format described in the module documentation.
+    fn from_str(s: &str) -> Result<BehaviourTree, String> {
+        let mut nodes: Vec<Node> = Vec::new();
+        // Nodes that later lines could be children of, as (indent, index).
+        let mut open: Vec<(usize, usize)> = Vec::new();
+        // How far each node's children are indented, once it has any.
+        let mut child_indents: Vec<Option<usize>> = Vec::new();
+
+        for (number, line) in s.lines().enumerate() {
+            let number = number + 1;
+            let line = line.split('#').next().unwrap().trim_end();
+            let text = line.trim_start();
+            if text.is_empty() {
+                continue;
+            }
+
+            let indent = &line[..line.len() - text.len()];
+            if indent.contains('\t') {
+                return Err(format!("line {}: indent with spaces, not tabs", number));
+            }
+            let indent = indent.len();
+            let kind = parse_kind(number, text)?;
+
+            while open.last().is_some_and(|&(open_indent, _)| open_indent >= indent) {
+                open.pop();
+            }
+
+            match open.last() {
+                Some(&(_, parent)) => {
+                    if is_leaf(&nodes[parent].kind) {
+                        return Err(format!("line {}: {} can't have children",
+                                           number, keyword(&nodes[parent].kind)));
+                    }
+
+                    match child_indents[parent] {
+                        Some(expected) if expected != indent => {
+                            return Err(format!("line {}: indent doesn't match the lines above",
+                                               number));
+                        },
+                        _ => child_indents[parent] = Some(indent),
+                    }
+
+                    let index = nodes.len();
+                    nodes[parent].children.push(index);
+                },
+                None if !nodes.is_empty() => {
+                    return Err(format!("line {}: a tree can only have one root", number));
+                },
+                None => {},
+            }
+
+            open.push((indent, nodes.len()));
+            nodes.push(Node::new(kind, number));
+            child_indents.push(None);
+        }
+
+        if nodes.is_empty() {
+            return Err("empty tree".to_owned());
+        }
+
+        for node in &nodes {
+            let count = node.children.len();
+            match node.kind {
+                Kind::Sequence | Kind::Selector if count == 0 => {
+                    return Err(format!("line {}: {} needs at least one child",
+                                       node.line, keyword(&node.kind)));
+                },
+                Kind::Invert | Kind::Succeed | Kind::Guard(..) if count != 1 => {
+                    return Err(format!("line {}: {} needs exactly one child",
+                                       node.line, keyword(&node.kind)));
+                },
+                _ => {},
+            }
+        }
+
+        Ok(BehaviourTree {
+            nodes,
+            blackboard: Blackboard::new(),
+        })
+    }
+}
+
+fn parse_kind(number: usize, text: &str) -> Result<Kind, String> {
+    let mut words = text.split_whitespace();
+    let keyword = words.next().unwrap();
+    let rest: Vec<&str> = words.collect();
+
+    let leaf = || match rest.split_first() {
+        Some((name, args)) => {
+            Ok((name.to_string(), args.iter().map(|&arg| parse_arg(arg)).collect()))
+        },
+        None => Err(format!("line {}: expected `{} name args...`", number, keyword)),
+    };
+
+    match keyword {
+        "sequence" | "selector" | "invert" | "succeed" if !rest.is_empty() => {
+            Err(format!("line {}: {} takes no arguments", number, keyword))
+        },
+        "sequence" => Ok(Kind::Sequence),
+        "selector" => Ok(Kind::Selector),
+        "invert" => Ok(Kind::Invert),
+        "succeed" => Ok(Kind::Succeed),
+        "wait" => match rest.as_slice() {
+            [seconds] => match seconds.parse::<f32>() {
+                Ok(seconds) if seconds >= 0. && seconds.is_finite() => Ok(Kind::Wait(seconds)),
+                _ => Err(format!("line {}: bad seconds: {}", number, seconds)),
+            },
+            _ => Err(format!("line {}: expected `wait seconds`", number)),
+        },
+        "guard" => leaf().map(|(name, args)| Kind::Guard(name, args)),
+        "condition" => leaf().map(|(name, args)| Kind::Condition(name, args)),
+        "action" => leaf().map(|(name, args)| Kind::Action(name, args)),
+        _ => Err(format!("line {}: unknown node: {}", number, keyword)),
+    }
+}
+
+// Words like ``nan`` and ``inf`` parse as floats, but they're meant as names.
+fn parse_arg(word: &str) -> Arg {
+    match word.parse::<f32>() {
+        Ok(value) if value.is_finite() => Arg::Number(value),
+        _ => Arg::Name(word.to_owned()),
+    }
+}
+
+fn is_leaf(kind: &Kind) -> bool {
+    matches!(*kind, Kind::Wait(_) | Kind::Condition(..) | Kind::Action(..))
+}
+
+fn keyword(kind: &Kind) -> &'static str {
+    match *kind {
+        Kind::Sequence => "sequence",
+        Kind::Selector => "selector",
+        Kind::Invert => "invert",
+        Kind::Succeed => "succeed",
+        Kind::Guard(..) => "guard",
+        Kind::Wait(_) => "wait",
+        Kind::Condition(..) => "condition",
+        Kind::Action(..) => "action",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_arg;
+    use super::super::{Arg, BehaviourTree, Kind};
+
+    fn error(source: &str) -> String {
+        source.parse::<BehaviourTree>().unwrap_err()
+    }
+
+    #[test]
+    fn reads_nested_nodes() {
+        let tree: BehaviourTree = "\
+# Attack when close, otherwise patrol.
+selector
+    guard near player 300
+        action attack player
+
+    sequence
+        action fly_to 0  # the first planet
+        wait 2.5
+".parse().unwrap();
+
+        let kinds: Vec<&Kind> = tree.nodes.iter().map(|node| &node.kind).collect();
+        assert_eq!(kinds, [
+            &Kind::Selector,
+            &Kind::Guard("near".to_owned(),
+                         vec![Arg::Name("player".to_owned()), Arg::Number(300.)]),
+            &Kind::Action("attack".to_owned(), vec![Arg::Name("player".to_owned())]),
+            &Kind::Sequence,
+            &Kind::Action("fly_to".to_owned(), vec![Arg::Number(0.)]),
+            &Kind::Wait(2.5),
+        ]);
+
+        assert_eq!(tree.nodes[0].children, [1, 3]);
+        assert_eq!(tree.nodes[1].children, [2]);
+        assert_eq!(tree.nodes[3].children, [4, 5]);
+        assert_eq!(tree.nodes[5].line, 8);
+    }
+
+    #[test]
+    fn only_finite_numbers_are_numbers() {
+        assert_eq!(parse_arg("-1.5"), Arg::Number(-1.5));
+        for word in &["nan", "NaN", "inf", "-inf", "infinity"] {
+            assert_eq!(parse_arg(word), Arg::Name(word.to_string()));
+        }
+        assert_eq!(error("wait inf"), "line 1: bad seconds: inf");
+    }
+
+    #[test]
+    fn rejects_bad_indentation() {
+        assert_eq!(error("sequence\n\taction a"), "line 2: indent with spaces, not tabs");
+        assert_eq!(error("sequence\n    action a\n  action b"),
+                   "line 3: indent doesn't match the lines above");
+        assert_eq!(error("sequence\n    action a\n        action b"),
+                   "line 3: action can't have children");
+    }
+
+    #[test]
+    fn rejects_several_roots() {
+        assert_eq!(error("action a\n\naction b"), "line 3: a tree can only have one root");
+        assert_eq!(error("  action a\naction b"), "line 2: a tree can only have one root");
+    }
+
+    #[test]
+    fn checks_child_counts() {
+        assert_eq!(error("sequence"), "line 1: sequence needs at least one child");
+        assert_eq!(error("selector\n    invert"), "line 2: invert needs exactly one child");
+        assert_eq!(error("succeed\n    action a\n    action b"),
+                   "line 1: succeed needs exactly one child");
+        assert_eq!(error("guard ready"), "line 1: guard needs exactly one child");
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        assert_eq!(error(""), "empty tree");
+        assert_eq!(error("# nothing here\n"), "empty tree");
+        assert_eq!(error("jump"), "line 1: unknown node: jump");
+        assert_eq!(error("selector now"), "line 1: selector takes no arguments");
+        assert_eq!(error("action"), "line 1: expected `action name args...`");
+        assert_eq!(error("wait"), "line 1: expected `wait seconds`");
+        assert_eq!(error("wait -1"), "line 1: bad seconds: -1");
+    }
+}
@@ -0,0 +1,263 @@
+//! Checksums of simulation state, for replays, regression tests and lockstep
+//! multiplayer.
+//!
+//! # Determinism
+//!
+//! Stepping ``EntityPhysics``, a ``PlanetManager`` and a ``ConstraintSolver``
+//! gives bit-identical results for identical inputs, as long as:
+//!
+//! * Every tick has the same ``dt``. Run ticks from a ``GameClock``, and record
+//!   inputs by tick number rather than by frame, since the number of ticks per
+//!   frame depends on real time.
+//! * Things happen in the same order. Planets update in the order they were added,
+//!   joints in the order of their ids, ``World`` entities in the order of their
+//!   slots, and ``SpatialHash::pairs`` in grid order.
+//!   Nothing in the step uses randomness or reads the clock.
+//! * The same build runs on the same kind of machine. Rust never fuses or
+//!   reorders float operations on its own, and ``+ - * /`` and ``sqrt`` are
+//!   exactly rounded everywhere, but ``powf``, ``exp``, ``sin`` and friends come
+//!   from the platform's math library and may differ in the last bit between
+//!   operating systems, so lockstep across platforms isn't guaranteed.
+//!
+//! To catch a divergence, hash the state with ``checksum`` after every tick and
+//! compare ``ChecksumLog``s between runs.
+
+use std::fmt;
+use std::hash::Hasher;
+use std::str::FromStr;
+use sfml::system::Vector2f;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// A 64-bit FNV-1a hasher for simulation state.
+/// Unlike the standard library's hashers, the result is the same on every run
+/// and every platform, and integers are always hashed as little endian.
+#[derive(Clone, Copy, Debug)]
+pub struct StateHasher {
+    state: u64,
+}
+
+impl StateHasher {
+    /// Create a hasher with nothing hashed yet.
+    pub fn new() -> StateHasher {
+        StateHasher {
+            state: FNV_OFFSET_BASIS,
+        }
+    }
+
+    /// Hash the exact bits of a float, so ``0.`` and ``-0.`` differ.
+    pub fn write_f32(&mut self, value: f32) {
+        self.write_u32(value.to_bits());
+    }
+
+    /// Hash the exact bits of a double.
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_u64(value.to_bits());
+    }
+
+    /// Hash both components of a vector.
+    pub fn write_vector(&mut self, value: Vector2f) {
+        self.write_f32(value.x);
+        self.write_f32(value.y);
+    }
+
+    /// Hash a flag.
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    /// Hash a string, prefixed with its length so neighbouring strings can't run together.
+    pub fn write_str(&mut self, value: &str) {
+        self.write_usize(value.len());
+        self.write(value.as_bytes());
+    }
+
+    /// Return the checksum of everything hashed so far.
+    pub fn checksum(&self) -> u64 {
+        self.state
+    }
+}
+
+impl Default for StateHasher {
+    fn default() -> StateHasher {
+        StateHasher::new()
+    }
+}
+
+impl Hasher for StateHasher {
+    fn finish(&self) -> u64 {
+        self.state
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state ^= byte as u64;
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+}
+
+/// Simulation state that can be fed to a ``StateHasher``.
+pub trait SimState {
+    /// Hash everything that affects how the simulation continues.
+    fn hash_state(&self, hasher: &mut StateHasher);
+}
+
+/// The checksum of one piece of simulation state.
+/// To cover several, feed them all to one ``StateHasher``.
+pub fn checksum<S: SimState + ?Sized>(state: &S) -> u64 {
+    let mut hasher = StateHasher::new();
+    state.hash_state(&mut hasher);
+    hasher.checksum()
+}
+
+impl<S: SimState> SimState for [S] {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.len());
+        for state in self {
+            state.hash_state(hasher);
+        }
+    }
+}
+
+/// A record of checksums by tick, to compare two runs of the same simulation.
+/// Converts to and from text, one ``tick checksum`` pair per line, so a
+/// reference run can be saved next to a replay.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChecksumLog {
+    entries: Vec<(u64, u64)>,
+}
+
+impl ChecksumLog {
+    /// Create an empty log.
+    pub fn new() -> ChecksumLog {
+        Default::default()
+    }
+
+    /// Record the checksum for a tick. Ticks should be recorded in increasing order.
+    pub fn record(&mut self, tick: u64, checksum: u64) {
+        self.entries.push((tick, checksum));
+    }
+
+    /// Return the checksum recorded for a tick.
+    pub fn get(&self, tick: u64) -> Option<u64> {
+        self.entries.binary_search_by_key(&tick, |&(t, _)| t)
+            .ok()
+            .map(|i| self.entries[i].1)
+    }
+
+    /// Access every ``(tick, checksum)`` pair, in the order they were recorded.
+    pub fn entries(&self) -> &[(u64, u64)] {
+        &self.entries
+    }
+
+    /// The first tick recorded in both logs whose checksums differ,
+    /// or ``None`` if they agree everywhere they overlap.
+    pub fn first_divergence(&self, other: &ChecksumLog) -> Option<u64> {
+        self.entries.iter()
+            .find(|&&(tick, checksum)| other.get(tick).is_some_and(|other| other != checksum))
+            .map(|&(tick, _)| tick)
+    }
+}
+
+impl fmt::Display for ChecksumLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &(tick, checksum) in &self.entries {
+            writeln!(f, "{} {:016x}", tick, checksum)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for ChecksumLog {
+    type Err = String;
+
+    /// Read a log written by ``Display``. Blank lines and ``#`` comments are skipped.
+    fn from_str(s: &str) -> Result<ChecksumLog, String> {
+        let mut log = ChecksumLog::new();
+
+        for (number, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let (tick, checksum) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(tick), Some(checksum), None) => (tick, checksum),
+                _ => return Err(format!("line {}: expected `tick checksum`", number + 1)),
+            };
+
+            let tick = tick.parse()
+                .map_err(|_| format!("line {}: bad tick: {}", number + 1, tick))?;
+            let checksum = u64::from_str_radix(checksum, 16)
+                .map_err(|_| format!("line {}: bad checksum: {}", number + 1, checksum))?;
+            log.record(tick, checksum);
+        }
+
+        Ok(log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChecksumLog;
+
+    fn log(entries: &[(u64, u64)]) -> ChecksumLog {
+        let mut log = ChecksumLog::new();
+        for &(tick, checksum) in entries {
+            log.record(tick, checksum);
+        }
+        log
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let original = log(&[(0, 0), (1, 0xdead_beef), (2, u64::MAX)]);
+        let text = original.to_string();
+        assert_eq!(text, "0 0000000000000000\n1 00000000deadbeef\n2 ffffffffffffffff\n");
+        assert_eq!(text.parse::<ChecksumLog>().unwrap(), original);
+    }
+
+    #[test]
+    fn skips_blanks_and_comments() {
+        let parsed: ChecksumLog = "# reference run\n\n0 0a  # first tick\n1 0b\n".parse().unwrap();
+        assert_eq!(parsed, log(&[(0, 0xa), (1, 0xb)]));
+    }
+
+    #[test]
+    fn finds_the_first_divergence() {
+        let reference = log(&[(0, 1), (1, 2), (2, 3), (3, 4)]);
+        let replay: ChecksumLog = "1 2\n2 5\n3 6\n".parse().unwrap();
+        assert_eq!(reference.first_divergence(&replay), Some(2));
+        assert_eq!(replay.first_divergence(&reference), Some(2));
+        assert_eq!(reference.first_divergence(&log(&[(0, 1), (9, 0)])), None);
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        let error = |text: &str| text.parse::<ChecksumLog>().unwrap_err();
+        assert_eq!(error("0 1\n2"), "line 2: expected `tick checksum`");
+        assert_eq!(error("0 1 2"), "line 1: expected `tick checksum`");
+        assert_eq!(error("-1 1"), "line 1: bad tick: -1");
+        assert_eq!(error("0 1\n1 xyz"), "line 2: bad checksum: xyz");
+    }
+}
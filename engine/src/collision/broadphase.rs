@@ -0,0 +1,335 @@
+//! Cheaply narrowing down which colliders might be touching.
+//!
+//! Testing every pair of shapes exactly is O(n²). A ``SpatialHash`` buckets
+//! bounding boxes into a uniform grid so that only things sharing a cell are
+//! considered, which keeps large numbers of small, similarly sized objects
+//! (asteroids, projectiles, ships) fast.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::Hash;
+use sfml::system::Vector2f;
+use super::shape::Bounds;
+
+type Cell = (i32, i32);
+
+/// A uniform grid of buckets indexing bounding boxes by a key.
+/// Keys are usually entity handles or indices.
+#[derive(Debug)]
+pub struct SpatialHash<K> {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<K>>,
+    entries: HashMap<K, Bounds>,
+}
+
+impl<K: Copy + Eq + Hash> SpatialHash<K> {
+    /// Create an empty hash. ``cell_size`` should be around the size of
+    /// a typical object; much smaller wastes memory on big objects, much larger
+    /// puts too many objects in each cell.
+    pub fn new(cell_size: f32) -> SpatialHash<K> {
+        assert!(cell_size > 0., "cell size must be positive");
+
+        SpatialHash {
+            cell_size,
+            cells: HashMap::new(),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Return the size of a cell.
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Register a key with its bounds, replacing any previous bounds.
+    pub fn insert(&mut self, key: K, bounds: Bounds) {
+        self.remove(key);
+
+        let (min, max) = self.cell_range(&bounds);
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                self.cells.entry((x, y)).or_default().push(key);
+            }
+        }
+
+        self.entries.insert(key, bounds);
+    }
+
+    /// Update the bounds of a key that moved. Same as ``insert``.
+    pub fn update(&mut self, key: K, bounds: Bounds) {
+        self.insert(key, bounds);
+    }
+
+    /// Unregister a key. Returns ``false`` if it wasn't registered.
+    pub fn remove(&mut self, key: K) -> bool {
+        let bounds = match self.entries.remove(&key) {
+            Some(bounds) => bounds,
+            None => return false,
+        };
+
+        let (min, max) = self.cell_range(&bounds);
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                let now_empty = match self.cells.get_mut(&(x, y)) {
+                    Some(cell) => {
+                        cell.retain(|&k| k != key);
+                        cell.is_empty()
+                    },
+                    None => false,
+                };
+
+                if now_empty {
+                    self.cells.remove(&(x, y));
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Unregister everything.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.entries.clear();
+    }
+
+    /// Return the bounds a key was registered with.
+    pub fn bounds(&self, key: K) -> Option<Bounds> {
+        self.entries.get(&key).cloned()
+    }
+
+    /// Return the number of registered keys.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Return whether nothing is registered.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Every pair of keys whose bounds overlap. Each pair is reported once.
+    /// These are only candidates: run the narrow phase on them to find real contacts.
+    ///
+    /// Pairs come out in the same order every run for the same sequence of
+    /// inserts and removes, so resolving them in order is deterministic.
+    pub fn pairs(&self) -> Vec<(K, K)> {
+        let mut pairs = Vec::new();
+
+        // Hash map order changes from run to run, so visit cells in grid order.
+        let mut cells: Vec<_> = self.cells.iter().collect();
+        cells.sort_unstable_by_key(|&(&cell, _)| cell);
+
+        for (&cell, keys) in cells {
+            for (i, &a) in keys.iter().enumerate() {
+                let a_bounds = &self.entries[&a];
+
+                for &b in &keys[i + 1..] {
+                    let b_bounds = &self.entries[&b];
+                    if !a_bounds.intersects(b_bounds) {
+                        continue;
+                    }
+
+                    // A pair can share many cells. Only report it from the cell
+                    // holding the corner of their overlap, so it comes out once.
+                    let overlap_min = Vector2f::new(a_bounds.min.x.max(b_bounds.min.x),
+                                                    a_bounds.min.y.max(b_bounds.min.y));
+                    if self.cell_of(overlap_min) == cell {
+                        pairs.push((a, b));
+                    }
+                }
+            }
+        }
+
+        pairs
+    }
+
+    /// Every key whose bounds overlap ``region``.
+    pub fn query_region(&self, region: &Bounds) -> Vec<K> {
+        let mut found = Vec::new();
+
+        let (min, max) = self.cell_range(region);
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                if let Some(keys) = self.cells.get(&(x, y)) {
+                    for &key in keys {
+                        if !found.contains(&key) && self.entries[&key].intersects(region) {
+                            found.push(key);
+                        }
+                    }
+                }
+            }
+        }
+
+        found
+    }
+
+    /// Every key whose bounds contain ``point``.
+    pub fn query_point<V: Into<Vector2f>>(&self, point: V) -> Vec<K> {
+        let point = point.into();
+
+        match self.cells.get(&self.cell_of(point)) {
+            Some(keys) => keys.iter()
+                .cloned()
+                .filter(|key| self.entries[key].contains(point))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The key whose bounds are closest to ``point``, no further than ``max_distance``.
+    /// Distance is measured to the edge of the bounds, and is zero inside them.
+    /// Returns the key along with its distance.
+    pub fn nearest<V: Into<Vector2f>>(&self, point: V, max_distance: f32) -> Option<(K, f32)> {
+        let point = point.into();
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        // Rings past the furthest occupied cell can't hold anything.
+        let center = self.cell_of(point);
+        let occupied_ring = self.cells.keys()
+            .map(|&cell| chebyshev_distance(center, cell))
+            .max()
+            .unwrap_or(0);
+        let max_ring = ((max_distance / self.cell_size).ceil() as i64).min(occupied_ring);
+
+        let mut best: Option<(K, f32)> = None;
+
+        // Walking rings only pays off while they cover fewer cells than are occupied.
+        // Otherwise check every occupied cell, in grid order so ties go the same way every run.
+        let ring_area = (2 * max_ring + 1).saturating_mul(2 * max_ring + 1);
+        if ring_area > self.cells.len() as i64 {
+            let mut cells: Vec<_> = self.cells.iter().collect();
+            cells.sort_unstable_by_key(|&(&cell, _)| cell);
+
+            for (_, keys) in cells {
+                for &key in keys {
+                    self.consider(point, max_distance, key, &mut best);
+                }
+            }
+
+            return best;
+        }
+
+        for ring in 0..=max_ring {
+            // Anything in this ring or further out is at least this far away.
+            let ring_distance = (ring - 1).max(0) as f32 * self.cell_size;
+            if let Some((_, best_distance)) = best {
+                if ring_distance > best_distance {
+                    break;
+                }
+            }
+
+            for cell in ring_cells(center, ring) {
+                if let Some(keys) = self.cells.get(&cell) {
+                    for &key in keys {
+                        self.consider(point, max_distance, key, &mut best);
+                    }
+                }
+            }
+        }
+
+        best
+    }
+
+    // Replace ``best`` with ``key`` if it is closer and within ``max_distance``.
+    fn consider(&self, point: Vector2f, max_distance: f32, key: K, best: &mut Option<(K, f32)>) {
+        let distance = distance_to_bounds(point, &self.entries[&key]);
+        let better = match *best {
+            Some((_, best_distance)) => distance < best_distance,
+            None => true,
+        };
+
+        if better && distance <= max_distance {
+            *best = Some((key, distance));
+        }
+    }
+
+    fn cell_of(&self, point: Vector2f) -> Cell {
+        ((point.x / self.cell_size).floor() as i32, (point.y / self.cell_size).floor() as i32)
+    }
+
+    fn cell_range(&self, bounds: &Bounds) -> (Cell, Cell) {
+        (self.cell_of(bounds.min), self.cell_of(bounds.max))
+    }
+}
+
+// The cells at exactly Chebyshev distance ``ring`` from ``center``,
+// leaving out any past the edge of the grid.
+fn ring_cells(center: Cell, ring: i64) -> Vec<Cell> {
+    let (x, y) = (center.0 as i64, center.1 as i64);
+    let mut cells = Vec::new();
+
+    if ring == 0 {
+        push_cell(&mut cells, x, y);
+        return cells;
+    }
+
+    for i in -ring..=ring {
+        push_cell(&mut cells, x + i, y - ring);
+        push_cell(&mut cells, x + i, y + ring);
+    }
+    for i in (-ring + 1)..ring {
+        push_cell(&mut cells, x - ring, y + i);
+        push_cell(&mut cells, x + ring, y + i);
+    }
+
+    cells
+}
+
+fn push_cell(cells: &mut Vec<Cell>, x: i64, y: i64) {
+    if let (Ok(x), Ok(y)) = (i32::try_from(x), i32::try_from(y)) {
+        cells.push((x, y));
+    }
+}
+
+fn chebyshev_distance(a: Cell, b: Cell) -> i64 {
+    (a.0 as i64 - b.0 as i64).abs().max((a.1 as i64 - b.1 as i64).abs())
+}
+
+fn distance_to_bounds(point: Vector2f, bounds: &Bounds) -> f32 {
+    let dx = (bounds.min.x - point.x).max(point.x - bounds.max.x).max(0.);
+    let dy = (bounds.min.y - point.y).max(point.y - bounds.max.y).max(0.);
+    (dx * dx + dy * dy).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use sfml::system::Vector2f;
+    use super::SpatialHash;
+    use super::super::shape::Bounds;
+
+    fn square(x: f32, y: f32, size: f32) -> Bounds {
+        Bounds::new(Vector2f::new(x, y), Vector2f::new(x + size, y + size))
+    }
+
+    #[test]
+    fn nearest_in_an_empty_hash_is_none() {
+        let hash: SpatialHash<u32> = SpatialHash::new(10.);
+        assert_eq!(hash.nearest((0., 0.), f32::INFINITY), None);
+    }
+
+    #[test]
+    fn nearest_with_no_limit_finds_far_entries() {
+        let mut hash = SpatialHash::new(10.);
+        hash.insert(1, square(1e6, 0., 5.));
+        hash.insert(2, square(-3e6, 0., 5.));
+
+        assert_eq!(hash.nearest((0., 0.), f32::INFINITY), Some((1, 1e6)));
+        assert_eq!(hash.nearest((5e6, 2.), f32::INFINITY).map(|(key, _)| key), Some(1));
+        assert!(hash.nearest((f32::MAX, -f32::MAX), f32::INFINITY).is_some());
+        assert_eq!(hash.nearest((0., 0.), 1e5), None);
+    }
+
+    #[test]
+    fn nearest_stops_at_the_closest_ring() {
+        let mut hash = SpatialHash::new(10.);
+        for i in 0..100 {
+            hash.insert(i, square(i as f32 * 20., 0., 5.));
+        }
+
+        assert_eq!(hash.nearest((203., 2.), f32::INFINITY), Some((10, 0.)));
+        assert_eq!(hash.nearest((212., 2.), 50.), Some((10, 7.)));
+    }
+}
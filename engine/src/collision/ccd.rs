@@ -0,0 +1,237 @@
+//! Continuous collision detection: finding when two moving shapes first touch
+//! during a tick, rather than only checking where they end up.
+//!
+//! Each body is swept from its pose at the start of the tick to its pose at
+//! the end by conservative advancement: the gap between the shapes is measured,
+//! and the sweep moves on by as much as that gap, since nothing on either body
+//! can cover it sooner. Near contact, steps are never shorter than the shapes'
+//! combined inner radius, which is still too short for them to pass through
+//! each other, so fast bodies always hit however thin the target. The first
+//! touching step is refined by bisection.
+
+use std::borrow::Cow;
+use sfml::system::Vector2f;
+use entity::EntityPhysics;
+use util::{self, Vector2Ext};
+use super::narrow_phase::{collide, separation};
+use super::shape::{Bounds, Collider, WorldShape};
+
+// The shortest step along a sweep, so shapes with no thickness still make progress.
+const MIN_STEP: f32 = 0.01;
+
+// Bisection steps used to refine the time of impact.
+const BISECTION_STEPS: u32 = 10;
+
+/// The motion of a body over one tick, as a straight line from one pose to another.
+/// Rotations are in degrees and turn the short way.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sweep {
+    /// Position at the start of the tick.
+    pub from_pos: Vector2f,
+
+    /// Rotation at the start of the tick.
+    pub from_rot: f32,
+
+    /// Position at the end of the tick.
+    pub to_pos: Vector2f,
+
+    /// Rotation at the end of the tick.
+    pub to_rot: f32,
+}
+
+impl Sweep {
+    /// The motion of a physics component over its last update.
+    pub fn of(phys: &EntityPhysics) -> Sweep {
+        Sweep {
+            from_pos: phys.prev_pos(),
+            from_rot: phys.prev_rot(),
+            to_pos: phys.pos(),
+            to_rot: phys.rot(),
+        }
+    }
+
+    /// A body that stays put.
+    pub fn fixed(pos: Vector2f, rot: f32) -> Sweep {
+        Sweep {
+            from_pos: pos,
+            from_rot: rot,
+            to_pos: pos,
+            to_rot: rot,
+        }
+    }
+
+    /// The pose a fraction ``t`` of the way through the sweep.
+    pub fn at(&self, t: f32) -> (Vector2f, f32) {
+        (self.from_pos + (self.to_pos - self.from_pos) * t,
+         self.from_rot + util::angle_delta(self.from_rot, self.to_rot) * t)
+    }
+
+    fn translation(&self) -> Vector2f {
+        self.to_pos - self.from_pos
+    }
+
+    fn turn(&self) -> f32 {
+        util::angle_delta(self.from_rot, self.to_rot)
+    }
+}
+
+/// The fraction of the way through the sweeps, from ``0`` to ``1``, at which two
+/// colliders first touch, or ``None`` if they never do. ``Some(0.)`` means they
+/// were already touching at the start.
+pub fn time_of_impact(a: &Collider, a_sweep: &Sweep, b: &Collider, b_sweep: &Sweep) -> Option<f32> {
+    // How far any point moves relative to the other body, counting the swing of
+    // the far edge of each shape as it turns.
+    let travel = (a_sweep.translation() - b_sweep.translation()).length()
+        + swing(a, a_sweep) + swing(b, b_sweep);
+    let min_step = a.shape().inner_radius() + b.shape().inner_radius();
+
+    first_touch(travel, min_step, |t| {
+        let (a_pos, a_rot) = a_sweep.at(t);
+        let (b_pos, b_rot) = b_sweep.at(t);
+        (a.world_shape_at(a_pos, a_rot), Cow::Owned(b.world_shape_at(b_pos, b_rot)))
+    })
+}
+
+/// Like ``time_of_impact``, but against a shape that stays where it is.
+/// Used to cast a collider through the world.
+pub fn time_of_impact_shape(collider: &Collider, sweep: &Sweep, target: &WorldShape) -> Option<f32> {
+    let travel = sweep.translation().length() + swing(collider, sweep);
+    let min_step = collider.shape().inner_radius() + target.inner_radius();
+
+    first_touch(travel, min_step, |t| {
+        let (pos, rot) = sweep.at(t);
+        (collider.world_shape_at(pos, rot), Cow::Borrowed(target))
+    })
+}
+
+// How far the far edge of a collider swings as it turns through a sweep.
+fn swing(collider: &Collider, sweep: &Sweep) -> f32 {
+    sweep.turn().to_radians().abs() * (collider.shape().bounding_radius() + collider.offset().length())
+}
+
+// Advance from 0 to 1 by the gap between the shapes ``at`` each point of the sweep,
+// where a gap covers ``travel`` per unit of sweep, then bisect between the last
+// free point and the first touching one.
+fn first_touch<'a, F>(travel: f32, min_step: f32, at: F) -> Option<f32>
+    where F: Fn(f32) -> (WorldShape, Cow<'a, WorldShape>)
+{
+    let touching = |t| {
+        let (a, b) = at(t);
+        collide(&a, &b).is_some()
+    };
+
+    if touching(0.) {
+        return Some(0.);
+    }
+    if travel <= 0. {
+        return None;
+    }
+
+    let min_step = min_step.max(MIN_STEP);
+    let mut free = 0.;
+    loop {
+        let (a, b) = at(free);
+        let step = separation(&a, &b).max(min_step) / travel;
+
+        // Rounding can swallow a tiny step late in the sweep, so always move on.
+        let mut t = (free + step).min(1.);
+        if t <= free {
+            t = (free + f32::EPSILON).min(1.);
+        }
+        if !touching(t) {
+            if t >= 1. {
+                return None;
+            }
+            free = t;
+            continue;
+        }
+
+        let mut hit = t;
+        for _ in 0..BISECTION_STEPS {
+            let mid = (free + hit) / 2.;
+            if touching(mid) {
+                hit = mid;
+            } else {
+                free = mid;
+            }
+        }
+        return Some(hit);
+    }
+}
+
+/// The bounding box of everywhere a collider goes during a sweep.
+/// Use it to find candidates in a ``SpatialHash`` before calling ``time_of_impact``.
+pub fn swept_bounds(collider: &Collider, sweep: &Sweep) -> Bounds {
+    let start = collider.world_shape_at(sweep.from_pos, sweep.from_rot).bounds();
+    let end = collider.world_shape_at(sweep.to_pos, sweep.to_rot).bounds();
+
+    // Turning can swing corners outside both ends, so allow for the worst case.
+    let margin = if sweep.turn() != 0. { collider.shape().bounding_radius() } else { 0. };
+    start.union(&end).expand(margin)
+}
+
+/// Move a physics component back to where it was a fraction ``t`` of the way
+/// through its last update, keeping its velocity. Used to place a bullet at
+/// its time of impact before resolving the collision.
+pub fn rewind(phys: &mut EntityPhysics, t: f32) {
+    let (pos, rot) = Sweep::of(phys).at(t);
+    let offset = pos - phys.pos();
+    let turn = util::angle_delta(phys.rot(), rot);
+    phys.translate(offset);
+    phys.turn(turn);
+}
+
+#[cfg(test)]
+mod tests {
+    use sfml::system::Vector2f;
+    use super::{time_of_impact, time_of_impact_shape, Sweep};
+    use super::super::shape::{Collider, Shape, WorldShape};
+
+    fn wall(x: f32, thickness: f32) -> WorldShape {
+        WorldShape::polygon(vec![Vector2f::new(x, -100.), Vector2f::new(x + thickness, -100.),
+                                 Vector2f::new(x + thickness, 100.), Vector2f::new(x, 100.)])
+    }
+
+    fn moving(from: (f32, f32), to: (f32, f32)) -> Sweep {
+        Sweep {
+            from_pos: from.into(),
+            from_rot: 0.,
+            to_pos: to.into(),
+            to_rot: 0.,
+        }
+    }
+
+    #[test]
+    fn fast_bodies_hit_thin_walls() {
+        let bullet = Collider::new(Shape::circle(0.5));
+        let sweep = moving((-50_000., 0.), (50_000., 0.));
+
+        let toi = time_of_impact_shape(&bullet, &sweep, &wall(1000., 0.01)).unwrap();
+        let (pos, _) = sweep.at(toi);
+        assert!(pos.x > 999. && pos.x < 1000.5, "hit at {}", pos.x);
+    }
+
+    #[test]
+    fn misses_report_none() {
+        let bullet = Collider::new(Shape::circle(0.5));
+        let sweep = moving((-50_000., 200.), (50_000., 200.));
+        assert_eq!(time_of_impact_shape(&bullet, &sweep, &wall(1000., 0.01)), None);
+
+        let beside = moving((-50_000., 0.), (900., 0.));
+        assert_eq!(time_of_impact_shape(&bullet, &beside, &wall(1000., 0.01)), None);
+    }
+
+    #[test]
+    fn bodies_crossing_each_other_touch() {
+        let a = Collider::new(Shape::obb((2., 2.)));
+        let b = Collider::new(Shape::circle(1.));
+        let a_sweep = moving((-10_000., 0.), (10_000., 0.));
+        let b_sweep = moving((10_000., 0.5), (-10_000., 0.5));
+
+        let toi = time_of_impact(&a, &a_sweep, &b, &b_sweep).unwrap();
+        assert!((toi - 0.49995).abs() < 1e-5, "touched at {}", toi);
+
+        let toi = time_of_impact(&a, &a_sweep, &b, &Sweep::fixed((0., 0.).into(), 0.)).unwrap();
+        assert!((toi - 0.4999).abs() < 1e-5, "touched at {}", toi);
+    }
+}
@@ -0,0 +1,28 @@
+//! Geometry for entities, and detecting when that geometry overlaps.
+//!
+//! A ``Collider`` describes the shape of an entity in its own coordinate frame.
+//! Each tick it is placed in the world using the entity's ``EntityPhysics``
+//! position and rotation, producing a ``WorldShape`` that the narrow phase
+//! tests against other shapes. Contacts are then resolved with impulses
+//! according to each collider's ``Material``. With many colliders, register
+//! them in a ``SpatialHash`` first so that only nearby pairs are tested.
+//! Fast bodies flagged as bullets are swept through the whole tick instead,
+//! so they can't skip over thin things.
+//!
+//! Each collider has a ``CollisionFilter`` saying which layers it is on and
+//! which it touches. The same filters pick what raycasts and other queries in
+//! ``query`` can find.
+
+pub use self::broadphase::SpatialHash;
+pub use self::ccd::{swept_bounds, time_of_impact, Sweep};
+pub use self::narrow_phase::{collide, Contact};
+pub use self::query::{Hit, Ray, ShapeIndex};
+pub use self::response::{collide_all, collide_entities, resolve, Material};
+pub use self::shape::{Bounds, Collider, CollisionFilter, Shape, WorldShape, ALL_LAYERS};
+
+pub mod broadphase;
+pub mod ccd;
+pub mod narrow_phase;
+pub mod query;
+pub mod response;
+pub mod shape;
@@ -0,0 +1,348 @@
+//! Exact intersection tests between pairs of ``WorldShape``s.
+//!
+//! Circles are tested directly, polygons use the separating axis theorem,
+//! and contact points for polygon pairs come from clipping the incident
+//! edge against the reference edge.
+
+use sfml::system::Vector2f;
+use super::shape::{Collider, WorldShape};
+use entity::EntityPhysics;
+use util::Vector2Ext;
+
+// Prefer shape A as the reference face unless B is clearly better,
+// which keeps contact points from flickering between the two.
+const REFERENCE_FACE_TOLERANCE: f32 = 0.005;
+
+/// Information about two overlapping shapes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contact {
+    /// Unit vector pointing from the first shape towards the second.
+    /// Moving the second shape by ``normal * depth`` separates them.
+    pub normal: Vector2f,
+
+    /// How far the shapes overlap along ``normal``.
+    pub depth: f32,
+
+    /// Points where the shapes touch, in world coordinates. One or two points.
+    pub points: Vec<Vector2f>,
+}
+
+impl Contact {
+    /// The same contact seen from the other shape.
+    pub fn flipped(self) -> Contact {
+        Contact {
+            normal: -self.normal,
+            ..self
+        }
+    }
+}
+
+/// Test two shapes for overlap, returning the contact if they do.
+/// Degenerate polygons never overlap anything.
+pub fn collide(a: &WorldShape, b: &WorldShape) -> Option<Contact> {
+    if a.is_degenerate() || b.is_degenerate() {
+        return None;
+    }
+
+    match (a, b) {
+        (&WorldShape::Circle { center: ca, radius: ra },
+         &WorldShape::Circle { center: cb, radius: rb }) => circle_circle(ca, ra, cb, rb),
+        (WorldShape::Polygon { vertices, normals },
+         &WorldShape::Circle { center, radius }) => polygon_circle(vertices, normals, center, radius),
+        (&WorldShape::Circle { center, radius },
+         WorldShape::Polygon { vertices, normals }) => {
+            polygon_circle(vertices, normals, center, radius).map(Contact::flipped)
+        },
+        (WorldShape::Polygon { vertices: va, normals: na },
+         WorldShape::Polygon { vertices: vb, normals: nb }) => polygon_polygon(va, na, vb, nb),
+    }
+}
+
+/// Test two colliders placed by their physics components.
+pub fn collide_bodies(a: &Collider, a_phys: &EntityPhysics,
+                      b: &Collider, b_phys: &EntityPhysics) -> Option<Contact> {
+    collide(&a.world_shape(a_phys), &b.world_shape(b_phys))
+}
+
+fn circle_circle(ca: Vector2f, ra: f32, cb: Vector2f, rb: f32) -> Option<Contact> {
+    let difference = cb - ca;
+    let dist_sq = difference.length_sq();
+    let radii = ra + rb;
+
+    if dist_sq >= radii * radii {
+        return None;
+    }
+
+    let dist = dist_sq.sqrt();
+    let normal = if dist > 0. {
+        difference / dist
+    } else {
+        // Concentric circles: any direction will do.
+        Vector2f::new(1., 0.)
+    };
+    let depth = radii - dist;
+
+    Some(Contact {
+        normal,
+        depth,
+        points: vec![ca + normal * (ra - depth / 2.)],
+    })
+}
+
+// The returned normal points from the polygon to the circle.
+fn polygon_circle(vertices: &[Vector2f], normals: &[Vector2f],
+                  center: Vector2f, radius: f32) -> Option<Contact> {
+    let (face, separation) = normals.iter()
+        .zip(vertices)
+        .map(|(n, &v)| n.dot(&(center - v)))
+        .enumerate()
+        .fold((0, f32::MIN), |best, (i, s)| if s > best.1 { (i, s) } else { best });
+
+    if separation > radius {
+        return None;
+    }
+
+    let v1 = vertices[face];
+    let v2 = vertices[(face + 1) % vertices.len()];
+    let normal = normals[face];
+
+    let face_contact = |normal: Vector2f, separation: f32| Contact {
+        normal,
+        depth: radius - separation,
+        points: vec![center - normal * separation],
+    };
+
+    // The center is inside the polygon, so the nearest face is the answer.
+    if separation <= 0. {
+        return Some(face_contact(normal, separation));
+    }
+
+    // Otherwise work out whether the closest feature is a corner or the face.
+    let corner_contact = |corner: Vector2f| {
+        let difference = center - corner;
+        let dist_sq = difference.length_sq();
+        if dist_sq > radius * radius {
+            return None;
+        }
+
+        let dist = dist_sq.sqrt();
+        Some(Contact {
+            normal: difference / dist,
+            depth: radius - dist,
+            points: vec![corner],
+        })
+    };
+
+    if (center - v1).dot(&(v2 - v1)) <= 0. {
+        corner_contact(v1)
+    } else if (center - v2).dot(&(v1 - v2)) <= 0. {
+        corner_contact(v2)
+    } else {
+        Some(face_contact(normal, separation))
+    }
+}
+
+fn polygon_polygon(va: &[Vector2f], na: &[Vector2f],
+                   vb: &[Vector2f], nb: &[Vector2f]) -> Option<Contact> {
+    let (face_a, sep_a) = max_separation(va, na, vb);
+    if sep_a > 0. {
+        return None;
+    }
+
+    let (face_b, sep_b) = max_separation(vb, nb, va);
+    if sep_b > 0. {
+        return None;
+    }
+
+    // The reference face is the one with the least penetration.
+    // The other polygon supplies the incident edge.
+    let (ref_v, ref_n, ref_face, inc_v, inc_n, flip) =
+        if sep_b > sep_a + REFERENCE_FACE_TOLERANCE {
+            (vb, nb, face_b, va, na, true)
+        } else {
+            (va, na, face_a, vb, nb, false)
+        };
+
+    let normal = ref_n[ref_face];
+    let r1 = ref_v[ref_face];
+    let r2 = ref_v[(ref_face + 1) % ref_v.len()];
+
+    // The incident face is the one most opposed to the reference normal.
+    let inc_face = (0..inc_n.len())
+        .min_by(|&i, &j| inc_n[i].dot(&normal).total_cmp(&inc_n[j].dot(&normal)))
+        .unwrap();
+    let incident = [inc_v[inc_face], inc_v[(inc_face + 1) % inc_v.len()]];
+
+    // Clip the incident edge to the side planes of the reference edge.
+    let tangent = (r2 - r1).normalize();
+    let clipped = clip_segment(&incident, -tangent, -tangent.dot(&r1))
+        .and_then(|seg| clip_segment(&seg, tangent, tangent.dot(&r2)))?;
+
+    // Keep only the points that are actually behind the reference face.
+    let mut points = Vec::with_capacity(2);
+    let mut depth: f32 = 0.;
+    for &p in &clipped {
+        let separation = normal.dot(&(p - r1));
+        if separation <= 0. {
+            points.push(p);
+            depth = depth.max(-separation);
+        }
+    }
+
+    if points.is_empty() {
+        return None;
+    }
+
+    Some(Contact {
+        normal: if flip { -normal } else { normal },
+        depth,
+        points,
+    })
+}
+
+// A lower bound on the distance between two shapes, which is zero or less when
+// they overlap. The gap between two shapes along any axis is never more than the
+// real distance, so this takes the widest gap along the axes SAT would test.
+pub(crate) fn separation(a: &WorldShape, b: &WorldShape) -> f32 {
+    if a.is_degenerate() || b.is_degenerate() {
+        return f32::INFINITY;
+    }
+
+    match (a, b) {
+        (&WorldShape::Circle { center: ca, radius: ra },
+         &WorldShape::Circle { center: cb, radius: rb }) => (cb - ca).length() - ra - rb,
+        (WorldShape::Polygon { vertices, normals }, &WorldShape::Circle { center, radius }) |
+        (&WorldShape::Circle { center, radius }, WorldShape::Polygon { vertices, normals }) => {
+            max_separation(vertices, normals, &[center]).1 - radius
+        },
+        (WorldShape::Polygon { vertices: va, normals: na },
+         WorldShape::Polygon { vertices: vb, normals: nb }) => {
+            max_separation(va, na, vb).1.max(max_separation(vb, nb, va).1)
+        },
+    }
+}
+
+// Find the face of ``vertices`` whose plane separates it furthest from ``other``.
+fn max_separation(vertices: &[Vector2f], normals: &[Vector2f], other: &[Vector2f]) -> (usize, f32) {
+    let mut best = (0, f32::MIN);
+
+    for (i, (n, v)) in normals.iter().zip(vertices).enumerate() {
+        let separation = other.iter()
+            .map(|&o| n.dot(&(o - *v)))
+            .fold(f32::MAX, f32::min);
+
+        if separation > best.1 {
+            best = (i, separation);
+        }
+    }
+
+    best
+}
+
+// Keep the part of a segment where ``dot(normal, p) <= offset``.
+fn clip_segment(segment: &[Vector2f; 2], normal: Vector2f, offset: f32) -> Option<[Vector2f; 2]> {
+    let d0 = normal.dot(&segment[0]) - offset;
+    let d1 = normal.dot(&segment[1]) - offset;
+
+    match (d0 <= 0., d1 <= 0.) {
+        (true, true) => Some(*segment),
+        (false, false) => None,
+        (inside_0, _) => {
+            let t = d0 / (d0 - d1);
+            let cut = segment[0] + (segment[1] - segment[0]) * t;
+            if inside_0 {
+                Some([segment[0], cut])
+            } else {
+                Some([cut, segment[1]])
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sfml::system::Vector2f;
+    use super::{collide, Contact};
+    use super::super::shape::WorldShape;
+    use util::Vector2Ext;
+
+    fn square(center: (f32, f32)) -> WorldShape {
+        let (x, y) = center;
+        WorldShape::polygon(vec![Vector2f::new(x - 1., y - 1.), Vector2f::new(x + 1., y - 1.),
+                                 Vector2f::new(x + 1., y + 1.), Vector2f::new(x - 1., y + 1.)])
+    }
+
+    fn circle(center: (f32, f32), radius: f32) -> WorldShape {
+        WorldShape::Circle { center: center.into(), radius }
+    }
+
+    fn assert_near(actual: Vector2f, expected: (f32, f32)) {
+        assert!((actual.x - expected.0).abs() < 1e-4 && (actual.y - expected.1).abs() < 1e-4,
+                "expected {:?}, got {:?}", expected, actual);
+    }
+
+    fn assert_contact(contact: &Contact, normal: (f32, f32), depth: f32, points: &[(f32, f32)]) {
+        assert_near(contact.normal, normal);
+        assert!((contact.depth - depth).abs() < 1e-4, "depth {}", contact.depth);
+        assert_eq!(contact.points.len(), points.len(), "points {:?}", contact.points);
+        for &point in points {
+            assert!(contact.points.iter().any(|p| (*p - Vector2f::from(point)).length_sq() < 1e-8),
+                    "missing {:?} in {:?}", point, contact.points);
+        }
+    }
+
+    #[test]
+    fn boxes_touch_face_to_face() {
+        let contact = collide(&square((0., 0.)), &square((1.5, 0.2))).unwrap();
+        assert_contact(&contact, (1., 0.), 0.5, &[(0.5, -0.8), (0.5, 1.)]);
+
+        let contact = collide(&square((1.5, 0.2)), &square((0., 0.))).unwrap();
+        assert_near(contact.normal, (-1., 0.));
+        assert!((contact.depth - 0.5).abs() < 1e-4);
+
+        assert_eq!(collide(&square((0., 0.)), &square((2.5, 0.))), None);
+    }
+
+    #[test]
+    fn circles_touch_polygon_faces_and_corners() {
+        let contact = collide(&square((0., 0.)), &circle((1.5, 0.), 1.)).unwrap();
+        assert_contact(&contact, (1., 0.), 0.5, &[(1., 0.)]);
+
+        let contact = collide(&circle((1.5, 0.), 1.), &square((0., 0.))).unwrap();
+        assert_contact(&contact, (-1., 0.), 0.5, &[(1., 0.)]);
+
+        let contact = collide(&square((0., 0.)), &circle((1.5, 1.5), 1.)).unwrap();
+        let diagonal = 0.5f32.sqrt();
+        assert_contact(&contact, (diagonal, diagonal), 1. - diagonal, &[(1., 1.)]);
+
+        assert_eq!(collide(&square((0., 0.)), &circle((1.8, 1.8), 1.)), None);
+    }
+
+    #[test]
+    fn polygon_corners_touch_faces() {
+        let triangle = WorldShape::polygon(vec![Vector2f::new(0., 0.8), Vector2f::new(1., 2.),
+                                                Vector2f::new(-1., 2.)]);
+
+        let contact = collide(&square((0., 0.)), &triangle).unwrap();
+        assert_contact(&contact, (0., 1.), 0.2, &[(0., 0.8)]);
+
+        let contact = collide(&triangle, &square((0., 0.))).unwrap();
+        assert_contact(&contact, (0., -1.), 0.2, &[(0., 0.8)]);
+    }
+
+    #[test]
+    fn degenerate_polygons_never_touch() {
+        let empty = WorldShape::Polygon { vertices: Vec::new(), normals: Vec::new() };
+        let segment = WorldShape::polygon(vec![Vector2f::new(-5., 0.), Vector2f::new(5., 0.)]);
+
+        assert!(!empty.bounds().intersects(&square((0., 0.)).bounds()));
+        assert_eq!(segment.bounds().min, Vector2f::new(-5., 0.));
+
+        for shape in &[empty, segment] {
+            assert!(shape.is_degenerate());
+            assert_eq!(collide(shape, &square((0., 0.))), None);
+            assert_eq!(collide(&circle((0., 0.), 1.), shape), None);
+            assert!(!shape.contains(Vector2f::new(0., 0.)));
+        }
+    }
+}
@@ -0,0 +1,334 @@
+//! Asking the world what is where: raycasts, shape casts and overlap tests,
+//! for AI, weapons and sensors.
+//!
+//! A ``ShapeIndex`` holds the world shapes of everything that can be found,
+//! each under a key with its ``CollisionFilter``. Re-insert entities after
+//! they move each tick. Planets answer the same queries through
+//! ``PlanetManager``. Queries take a filter whose mask picks which layers to
+//! look at; what is found doesn't have to accept the query in return.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use sfml::system::Vector2f;
+use entity::EntityPhysics;
+use util::Vector2Ext;
+use super::broadphase::SpatialHash;
+use super::ccd::{self, Sweep};
+use super::narrow_phase::collide;
+use super::shape::{Bounds, Collider, CollisionFilter, WorldShape};
+
+// Regions covering more grid cells than this are checked against every shape
+// instead of walking the grid, so long rays stay cheap.
+const MAX_QUERY_CELLS: f32 = 1024.;
+
+/// A half-line from ``origin`` along ``direction``, cut off at ``max_distance``.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    /// Where the ray starts.
+    pub origin: Vector2f,
+
+    /// Unit vector the ray points along.
+    pub direction: Vector2f,
+
+    /// How far the ray reaches. May be infinite.
+    pub max_distance: f32,
+}
+
+impl Ray {
+    /// Create a ray. ``direction`` is normalized, and must not be zero.
+    pub fn new<V: Into<Vector2f>, D: Into<Vector2f>>(origin: V, direction: D, max_distance: f32)
+                                                     -> Ray {
+        let direction = direction.into();
+        assert!(direction != Vector2f::new(0., 0.), "a ray needs a direction");
+
+        Ray {
+            origin: origin.into(),
+            direction: direction.normalize(),
+            max_distance,
+        }
+    }
+
+    /// Create a ray from one point to another. The points must differ.
+    pub fn between<V: Into<Vector2f>>(from: V, to: V) -> Ray {
+        let from = from.into();
+        let offset = to.into() - from;
+        Ray::new(from, offset, offset.length())
+    }
+
+    /// The point ``distance`` along the ray.
+    pub fn point_at(&self, distance: f32) -> Vector2f {
+        self.origin + self.direction * distance
+    }
+
+    /// The bounding box of the ray.
+    pub fn bounds(&self) -> Bounds {
+        let end = self.point_at(self.max_distance);
+        Bounds::new(Vector2f::new(self.origin.x.min(end.x), self.origin.y.min(end.y)),
+                    Vector2f::new(self.origin.x.max(end.x), self.origin.y.max(end.y)))
+    }
+}
+
+/// Where a query touched something.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    /// How far along the ray, or how far the cast collider traveled, before touching.
+    pub distance: f32,
+
+    /// Where the two touch, in world coordinates.
+    pub point: Vector2f,
+
+    /// Unit normal of the surface that was hit, pointing back out of it.
+    pub normal: Vector2f,
+}
+
+/// Where a ray first enters a shape, or ``None`` if it misses or the shape is
+/// beyond ``max_distance``. A ray starting inside the shape hits it at
+/// distance zero, with the normal facing back along the ray.
+/// Degenerate polygons are never hit.
+pub fn raycast(ray: &Ray, shape: &WorldShape) -> Option<Hit> {
+    if shape.is_degenerate() {
+        return None;
+    }
+
+    let (distance, normal) = match *shape {
+        WorldShape::Circle { center, radius } => {
+            let m = ray.origin - center;
+            let c = m.length_sq() - radius * radius;
+            if c <= 0. {
+                (0., -ray.direction)
+            } else {
+                // Solve |m + t * direction| = radius for the nearer t.
+                let b = m.dot(&ray.direction);
+                let discriminant = b * b - c;
+                if b > 0. || discriminant < 0. {
+                    return None;
+                }
+
+                let distance = -b - discriminant.sqrt();
+                (distance, (ray.point_at(distance) - center) / radius)
+            }
+        },
+        WorldShape::Polygon { ref vertices, ref normals } => {
+            // Clip the ray against the inside of each edge in turn.
+            let mut enter = 0.;
+            let mut exit = ray.max_distance;
+            let mut normal = -ray.direction;
+
+            for (&v, &n) in vertices.iter().zip(normals) {
+                let facing = n.dot(&ray.direction);
+                let gap = n.dot(&(v - ray.origin));
+
+                if facing == 0. {
+                    if gap < 0. {
+                        return None;
+                    }
+                } else if facing < 0. {
+                    if gap / facing > enter {
+                        enter = gap / facing;
+                        normal = n;
+                    }
+                } else if gap / facing < exit {
+                    exit = gap / facing;
+                }
+
+                if enter > exit {
+                    return None;
+                }
+            }
+
+            (enter, normal)
+        },
+    };
+
+    if distance > ray.max_distance {
+        return None;
+    }
+
+    Some(Hit {
+        distance,
+        point: ray.point_at(distance),
+        normal,
+    })
+}
+
+/// Where a collider moving along a sweep first touches a shape that stays
+/// put, or ``None`` if it never does. Found by advancing along the sweep,
+/// like ``ccd::time_of_impact``.
+pub fn shape_cast(collider: &Collider, sweep: &Sweep, target: &WorldShape) -> Option<Hit> {
+    let toi = ccd::time_of_impact_shape(collider, sweep, target)?;
+    let (pos, rot) = sweep.at(toi);
+
+    let (point, normal) = match collide(&collider.world_shape_at(pos, rot), target) {
+        Some(contact) => {
+            let point = contact.points.iter().fold(Vector2f::new(0., 0.), |acc, &p| acc + p)
+                / contact.points.len() as f32;
+            (point, -contact.normal)
+        },
+
+        // Bisection always ends on a touching pose, so this shouldn't happen.
+        None => (pos, (sweep.from_pos - sweep.to_pos).normalize()),
+    };
+
+    Some(Hit {
+        distance: (sweep.to_pos - sweep.from_pos).length() * toi,
+        point,
+        normal,
+    })
+}
+
+/// Indexes world shapes by key for queries. Keys are usually entity handles or indices.
+#[derive(Debug)]
+pub struct ShapeIndex<K> {
+    hash: SpatialHash<K>,
+    entries: Vec<(K, WorldShape, CollisionFilter)>,
+    slots: HashMap<K, usize>,
+}
+
+impl<K: Copy + Eq + Hash> ShapeIndex<K> {
+    /// Create an empty index. See ``SpatialHash::new`` for choosing ``cell_size``.
+    pub fn new(cell_size: f32) -> ShapeIndex<K> {
+        ShapeIndex {
+            hash: SpatialHash::new(cell_size),
+            entries: Vec::new(),
+            slots: HashMap::new(),
+        }
+    }
+
+    /// Register a collider placed by its physics component, replacing whatever
+    /// the key had before.
+    pub fn insert(&mut self, key: K, collider: &Collider, phys: &EntityPhysics) {
+        self.insert_shape(key, collider.world_shape(phys), collider.filter());
+    }
+
+    /// Register a shape that is already in world coordinates.
+    pub fn insert_shape(&mut self, key: K, shape: WorldShape, filter: CollisionFilter) {
+        self.hash.insert(key, shape.bounds());
+
+        match self.slots.get(&key) {
+            Some(&slot) => self.entries[slot] = (key, shape, filter),
+            None => {
+                self.slots.insert(key, self.entries.len());
+                self.entries.push((key, shape, filter));
+            },
+        }
+    }
+
+    /// Unregister a key. Returns ``false`` if it wasn't registered.
+    pub fn remove(&mut self, key: K) -> bool {
+        let slot = match self.slots.remove(&key) {
+            Some(slot) => slot,
+            None => return false,
+        };
+
+        self.hash.remove(key);
+        self.entries.swap_remove(slot);
+        if let Some(&(moved, _, _)) = self.entries.get(slot) {
+            self.slots.insert(moved, slot);
+        }
+
+        true
+    }
+
+    /// Unregister everything.
+    pub fn clear(&mut self) {
+        self.hash.clear();
+        self.entries.clear();
+        self.slots.clear();
+    }
+
+    /// Access the shape a key was registered with.
+    pub fn shape(&self, key: K) -> Option<&WorldShape> {
+        self.slots.get(&key).map(|&slot| &self.entries[slot].1)
+    }
+
+    /// Return the number of registered keys.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Return whether nothing is registered.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The nearest shape a ray hits, skipping ``ignore``, such as whoever is
+    /// firing it.
+    pub fn raycast(&self, ray: &Ray, filter: &CollisionFilter, ignore: Option<K>)
+                   -> Option<(K, Hit)> {
+        nearest(self.raycast_all(ray, filter, ignore))
+    }
+
+    /// Every shape a ray hits, nearest first.
+    pub fn raycast_all(&self, ray: &Ray, filter: &CollisionFilter, ignore: Option<K>)
+                       -> Vec<(K, Hit)> {
+        let mut hits: Vec<_> = self.candidates(&ray.bounds(), filter, ignore)
+            .filter_map(|(key, shape)| raycast(ray, shape).map(|hit| (key, hit)))
+            .collect();
+        sort_hits(&mut hits);
+        hits
+    }
+
+    /// The first shape a collider touches as it moves along a sweep. The
+    /// collider's own filter picks which layers it can touch.
+    pub fn shape_cast(&self, collider: &Collider, sweep: &Sweep, ignore: Option<K>)
+                      -> Option<(K, Hit)> {
+        let region = ccd::swept_bounds(collider, sweep);
+        nearest(self.candidates(&region, &collider.filter(), ignore)
+            .filter_map(|(key, shape)| shape_cast(collider, sweep, shape).map(|hit| (key, hit))))
+    }
+
+    /// Every shape containing a point.
+    pub fn overlap_point<V: Into<Vector2f>>(&self, point: V, filter: &CollisionFilter,
+                                            ignore: Option<K>) -> Vec<K> {
+        let point = point.into();
+        self.candidates(&Bounds::new(point, point), filter, ignore)
+            .filter(|&(_, shape)| shape.contains(point))
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    /// Every shape overlapping an area.
+    pub fn overlap_shape(&self, area: &WorldShape, filter: &CollisionFilter, ignore: Option<K>)
+                         -> Vec<K> {
+        self.candidates(&area.bounds(), filter, ignore)
+            .filter(|&(_, shape)| collide(area, shape).is_some())
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    // Shapes that might touch ``region`` and pass the filter, in a fixed order.
+    fn candidates<'a>(&'a self, region: &Bounds, filter: &'a CollisionFilter, ignore: Option<K>)
+                      -> impl Iterator<Item = (K, &'a WorldShape)> + 'a {
+        let slots: Vec<usize> = if is_small_region(self.hash.cell_size(), region) {
+            self.hash.query_region(region).iter().map(|key| self.slots[key]).collect()
+        } else {
+            (0..self.entries.len()).collect()
+        };
+
+        slots.into_iter()
+            .map(move |slot| &self.entries[slot])
+            .filter(move |&&(key, _, ref other)| Some(key) != ignore && filter.accepts(other))
+            .map(|&(key, ref shape, _)| (key, shape))
+    }
+}
+
+// Whether a grid with cells of ``cell_size`` can cheaply list what's in ``region``.
+// Infinite regions, such as from unlimited rays, never can.
+pub(crate) fn is_small_region(cell_size: f32, region: &Bounds) -> bool {
+    let cells = ((region.max.x - region.min.x) / cell_size + 1.)
+        * ((region.max.y - region.min.y) / cell_size + 1.);
+    cells <= MAX_QUERY_CELLS
+}
+
+// The hit with the smallest distance, preferring the earliest on ties.
+pub(crate) fn nearest<K, I: IntoIterator<Item = (K, Hit)>>(hits: I) -> Option<(K, Hit)> {
+    hits.into_iter().fold(None, |best: Option<(K, Hit)>, (key, hit)| match best {
+        Some((_, ref best_hit)) if best_hit.distance <= hit.distance => best,
+        _ => Some((key, hit)),
+    })
+}
+
+// Order hits nearest first, keeping the original order on ties.
+pub(crate) fn sort_hits<K>(hits: &mut [(K, Hit)]) {
+    hits.sort_by(|a, b| a.1.distance.total_cmp(&b.1.distance));
+}
@@ -0,0 +1,202 @@
+//! Physical reaction to contacts: bouncing, sliding friction and
+//! pushing overlapping bodies apart.
+
+use sfml::system::Vector2f;
+use super::broadphase::SpatialHash;
+use super::ccd::{self, Sweep};
+use super::narrow_phase::Contact;
+use entity::{Entity, EntityPhysics};
+use util::Vector2Ext;
+
+// Overlap that is allowed to remain, so resting contacts don't jitter.
+const PENETRATION_SLOP: f32 = 0.5;
+
+// Fraction of the remaining overlap removed each time a contact is resolved.
+const CORRECTION_PERCENT: f32 = 0.6;
+
+// Approach speeds below this don't bounce, which lets objects come to rest.
+const RESTING_SPEED: f32 = 1.;
+
+/// Surface properties of a collider.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Material {
+    /// Bounciness. ``0`` is perfectly inelastic, ``1`` perfectly elastic.
+    pub restitution: f32,
+
+    /// Coulomb friction coefficient.
+    pub friction: f32,
+}
+
+impl Material {
+    /// Create a new material.
+    pub fn new(restitution: f32, friction: f32) -> Material {
+        Material {
+            restitution,
+            friction,
+        }
+    }
+
+    /// Combine two materials for a contact between them.
+    /// The bouncier restitution wins, and friction is the geometric mean.
+    pub fn combine(&self, other: &Material) -> Material {
+        Material {
+            restitution: self.restitution.max(other.restitution),
+            friction: (self.friction * other.friction).sqrt(),
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material::new(0.2, 0.4)
+    }
+}
+
+/// Apply collision impulses to two bodies touching according to ``contact``,
+/// whose normal must point from ``a`` to ``b``. Normal impulses stop the bodies
+/// approaching (with some bounce), friction impulses oppose sliding, and both
+/// are applied at the contact points so they induce spin. Finally the bodies
+/// are nudged apart so they don't sink into each other.
+pub fn resolve(a: &mut EntityPhysics, a_material: &Material,
+               b: &mut EntityPhysics, b_material: &Material, contact: &Contact) {
+    let inv_mass_sum = a.inv_mass() + b.inv_mass();
+    if inv_mass_sum == 0. {
+        return;
+    }
+
+    let material = a_material.combine(b_material);
+    let normal = contact.normal;
+    let share = 1. / contact.points.len() as f32;
+
+    for &point in &contact.points {
+        let ra = point - a.pos();
+        let rb = point - b.pos();
+
+        //
+        // Normal
+        //
+        let rel_vel = b.vel_at(point) - a.vel_at(point);
+        let approach = rel_vel.dot(&normal);
+        if approach > 0. {
+            // Already separating.
+            continue;
+        }
+
+        let restitution = if -approach < RESTING_SPEED { 0. } else { material.restitution };
+        let normal_impulse = -(1. + restitution) * approach
+            / inv_effective_mass(a, b, ra, rb, normal) * share;
+
+        a.apply_impulse_at(normal * -normal_impulse, point);
+        b.apply_impulse_at(normal * normal_impulse, point);
+
+        //
+        // Friction
+        //
+        let rel_vel = b.vel_at(point) - a.vel_at(point);
+        let sliding = rel_vel - normal * rel_vel.dot(&normal);
+        let sliding_speed = sliding.length();
+        if sliding_speed <= f32::EPSILON {
+            continue;
+        }
+
+        let tangent = sliding / sliding_speed;
+        let max_friction = material.friction * normal_impulse;
+        let friction_impulse = (sliding_speed / inv_effective_mass(a, b, ra, rb, tangent) * share)
+            .min(max_friction);
+
+        a.apply_impulse_at(tangent * friction_impulse, point);
+        b.apply_impulse_at(tangent * -friction_impulse, point);
+    }
+
+    //
+    // Positional correction
+    //
+    let correction = (contact.depth - PENETRATION_SLOP).max(0.) * CORRECTION_PERCENT / inv_mass_sum;
+    let a_inv_mass = a.inv_mass();
+    let b_inv_mass = b.inv_mass();
+    a.translate(normal * (-correction * a_inv_mass));
+    b.translate(normal * (correction * b_inv_mass));
+}
+
+/// Detect and resolve a collision between two entities with colliders.
+/// Returns the contact if they were touching. Colliders whose filters don't
+/// accept each other pass straight through.
+/// If either is a bullet, both are swept through their last update, and moved
+/// back to where they first touched before resolving, unless they were already
+/// touching at the start.
+pub fn collide_entities<A: Entity + ?Sized, B: Entity + ?Sized>(a: &mut A, b: &mut B)
+                                                               -> Option<Contact> {
+    if !a.collider()?.filter().collides_with(&b.collider()?.filter()) {
+        return None;
+    }
+
+    if a.phys().is_bullet() || b.phys().is_bullet() {
+        let toi = ccd::time_of_impact(a.collider()?, &Sweep::of(a.phys()),
+                                      b.collider()?, &Sweep::of(b.phys()))?;
+        // Rewinding a resting contact to the start would pin it in place.
+        if toi > 0. {
+            ccd::rewind(a.phys_mut(), toi);
+            ccd::rewind(b.phys_mut(), toi);
+        }
+    }
+
+    let (contact, a_material, b_material) = {
+        let (a_collider, b_collider) = match (a.collider(), b.collider()) {
+            (Some(a_collider), Some(b_collider)) => (a_collider, b_collider),
+            _ => return None,
+        };
+
+        let contact = super::collide(&a_collider.world_shape(a.phys()),
+                                     &b_collider.world_shape(b.phys()))?;
+        (contact, *a_collider.material(), *b_collider.material())
+    };
+
+    resolve(a.phys_mut(), &a_material, b.phys_mut(), &b_material, &contact);
+    Some(contact)
+}
+
+/// Detect and resolve collisions between every pair of ``entities`` with
+/// ``collide_entities``. Candidates come from a ``SpatialHash`` of everywhere
+/// each entity went during its last update, so bullets are still found, and
+/// pairs are resolved in the same order every run.
+pub fn collide_all<E: Entity + ?Sized>(entities: &mut [&mut E]) {
+    let bounds: Vec<_> = entities.iter()
+        .map(|entity| {
+            entity.collider().map(|collider| ccd::swept_bounds(collider, &Sweep::of(entity.phys())))
+        })
+        .collect();
+
+    // Cells the size of the largest entity keep big ones in few cells.
+    let cell_size = bounds.iter()
+        .flatten()
+        .map(|bounds| (bounds.max.x - bounds.min.x).max(bounds.max.y - bounds.min.y))
+        .fold(0., f32::max);
+    if !(cell_size > 0. && cell_size.is_finite()) {
+        return;
+    }
+
+    let mut hash = SpatialHash::new(cell_size);
+    for (i, bounds) in bounds.into_iter().enumerate() {
+        if let Some(bounds) = bounds {
+            hash.insert(i, bounds);
+        }
+    }
+
+    for (i, j) in hash.pairs() {
+        let (i, j) = (i.min(j), i.max(j));
+        let (left, right) = entities.split_at_mut(j);
+        collide_entities(&mut *left[i], &mut *right[0]);
+    }
+}
+
+// The reciprocal of the mass felt by an impulse along ``direction`` at offsets ``ra`` and ``rb``.
+pub(crate) fn inv_effective_mass(a: &EntityPhysics, b: &EntityPhysics,
+                                 ra: Vector2f, rb: Vector2f, direction: Vector2f) -> f32 {
+    let ra_cross = ra.cross(&direction);
+    let rb_cross = rb.cross(&direction);
+
+    a.inv_mass()
+        + b.inv_mass()
+        + ra_cross * ra_cross * a.inv_rotational_inertia()
+        + rb_cross * rb_cross * b.inv_rotational_inertia()
+}
@@ -0,0 +1,392 @@
+//! Collision shapes and their placement in the world.
+
+use sfml::system::Vector2f;
+use super::response::Material;
+use entity::EntityPhysics;
+use util::Vector2Ext;
+
+/// The geometry of a collider, in the entity's local coordinate frame.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    /// A circle centered on the collider.
+    Circle {
+        /// Radius of the circle.
+        radius: f32,
+    },
+
+    /// An axis-aligned box. Its center follows the entity, but it never rotates,
+    /// which makes it cheap and a good fit for things that don't spin.
+    Aabb {
+        /// Half of the box's width and height.
+        half_extents: Vector2f,
+    },
+
+    /// A box that rotates with the entity.
+    Obb {
+        /// Half of the box's width and height, before rotation.
+        half_extents: Vector2f,
+    },
+
+    /// A convex polygon that rotates with the entity.
+    /// Vertices may be in either winding order, but must be convex.
+    /// With fewer than three vertices it has no area and never collides.
+    Polygon {
+        /// Vertices relative to the collider's center.
+        vertices: Vec<Vector2f>,
+    },
+}
+
+impl Shape {
+    /// Create a circle.
+    pub fn circle(radius: f32) -> Shape {
+        Shape::Circle { radius }
+    }
+
+    /// Create an axis-aligned box from its full width and height.
+    pub fn aabb<V: Into<Vector2f>>(size: V) -> Shape {
+        Shape::Aabb { half_extents: size.into() / 2. }
+    }
+
+    /// Create a rotating box from its full width and height.
+    pub fn obb<V: Into<Vector2f>>(size: V) -> Shape {
+        Shape::Obb { half_extents: size.into() / 2. }
+    }
+
+    /// Create a convex polygon. Panics if there are fewer than three vertices.
+    pub fn polygon(vertices: Vec<Vector2f>) -> Shape {
+        assert!(vertices.len() >= 3, "a polygon needs at least three vertices");
+        Shape::Polygon { vertices }
+    }
+
+    /// The radius of the smallest circle around the collider's center that
+    /// contains the whole shape.
+    pub fn bounding_radius(&self) -> f32 {
+        match *self {
+            Shape::Circle { radius } => radius,
+            Shape::Aabb { half_extents } | Shape::Obb { half_extents } => half_extents.length(),
+            Shape::Polygon { ref vertices } => {
+                vertices.iter().map(|v| v.length()).fold(0., f32::max)
+            },
+        }
+    }
+
+    /// The radius of the largest circle around the collider's center that fits
+    /// inside the shape. Polygons are assumed to contain their center.
+    pub fn inner_radius(&self) -> f32 {
+        match *self {
+            Shape::Circle { radius } => radius,
+            Shape::Aabb { half_extents } | Shape::Obb { half_extents } => {
+                half_extents.x.min(half_extents.y)
+            },
+            Shape::Polygon { ref vertices } if vertices.len() < 3 => 0.,
+            Shape::Polygon { ref vertices } => {
+                (0..vertices.len()).map(|i| {
+                    let v1 = vertices[i];
+                    let edge = vertices[(i + 1) % vertices.len()] - v1;
+                    edge.cross(&v1).abs() / edge.length()
+                }).fold(f32::INFINITY, f32::min)
+            },
+        }
+    }
+}
+
+/// A ``Shape`` attached to an entity.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Collider {
+    shape: Shape,
+    offset: Vector2f,
+    material: Material,
+    filter: CollisionFilter,
+}
+
+impl Collider {
+    /// Create a collider centered on the entity's physics position.
+    pub fn new(shape: Shape) -> Collider {
+        Self::with_offset(shape, (0., 0.))
+    }
+
+    /// Create a collider whose center is offset from the entity's physics position.
+    /// The offset is in the entity's local frame, so it turns as the entity rotates.
+    pub fn with_offset<V: Into<Vector2f>>(shape: Shape, offset: V) -> Collider {
+        Collider {
+            shape,
+            offset: offset.into(),
+            material: Default::default(),
+            filter: Default::default(),
+        }
+    }
+
+    /// Set the surface material used for collision response.
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    /// Set which layers the collider is on and which it touches.
+    pub fn set_filter(&mut self, filter: CollisionFilter) {
+        self.filter = filter;
+    }
+
+    /// Access the shape.
+    pub fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    /// Return the local offset of the shape's center.
+    pub fn offset(&self) -> Vector2f {
+        self.offset
+    }
+
+    /// Access the surface material.
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// Return the collider's layer filter.
+    pub fn filter(&self) -> CollisionFilter {
+        self.filter
+    }
+
+    /// Place the shape in the world according to a physics component.
+    pub fn world_shape(&self, phys: &EntityPhysics) -> WorldShape {
+        self.world_shape_at(phys.pos(), phys.rot())
+    }
+
+    /// Place the shape in the world at an explicit position and rotation in degrees.
+    pub fn world_shape_at(&self, pos: Vector2f, rot: f32) -> WorldShape {
+        let center = pos + self.offset.rotate(rot);
+
+        match self.shape {
+            Shape::Circle { radius } => WorldShape::Circle { center, radius },
+            Shape::Aabb { half_extents } => WorldShape::polygon(box_corners(half_extents)
+                .iter()
+                .map(|&v| center + v)
+                .collect()),
+            Shape::Obb { half_extents } => WorldShape::polygon(box_corners(half_extents)
+                .iter()
+                .map(|v| center + v.rotate(rot))
+                .collect()),
+            Shape::Polygon { ref vertices } => WorldShape::polygon(vertices
+                .iter()
+                .map(|v| center + v.rotate(rot))
+                .collect()),
+        }
+    }
+
+    /// World space bounding box of the collider.
+    pub fn bounds(&self, phys: &EntityPhysics) -> Bounds {
+        self.world_shape(phys).bounds()
+    }
+}
+
+/// Every collision layer.
+pub const ALL_LAYERS: u32 = !0;
+
+/// Which collision layers a collider is on, and which layers it touches.
+/// Each bit of ``layers`` and ``mask`` is one layer, so there are 32 to share
+/// out between ships, projectiles, debris and so on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CollisionFilter {
+    /// The layers the collider is on.
+    pub layers: u32,
+
+    /// The layers the collider touches.
+    pub mask: u32,
+}
+
+impl CollisionFilter {
+    /// Create a filter from its layers and mask.
+    pub fn new(layers: u32, mask: u32) -> CollisionFilter {
+        CollisionFilter { layers, mask }
+    }
+
+    /// Test whether this filter's mask takes in any of ``other``'s layers.
+    /// Queries use this one-sided test, so a ray only has to care about what it
+    /// hits, not the other way around.
+    pub fn accepts(&self, other: &CollisionFilter) -> bool {
+        self.mask & other.layers != 0
+    }
+
+    /// Test whether two colliders touch: each must accept the other.
+    pub fn collides_with(&self, other: &CollisionFilter) -> bool {
+        self.accepts(other) && other.accepts(self)
+    }
+}
+
+impl Default for CollisionFilter {
+    /// On the first layer, touching every layer.
+    fn default() -> CollisionFilter {
+        CollisionFilter::new(1, ALL_LAYERS)
+    }
+}
+
+/// A shape placed in world coordinates, ready for intersection tests.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WorldShape {
+    /// A circle.
+    Circle {
+        /// Center of the circle.
+        center: Vector2f,
+
+        /// Radius of the circle.
+        radius: f32,
+    },
+
+    /// A convex polygon. Boxes become polygons once placed.
+    /// With fewer than three vertices it is degenerate; see ``is_degenerate``.
+    Polygon {
+        /// Vertices in world coordinates.
+        vertices: Vec<Vector2f>,
+
+        /// Outward unit normal of the edge from ``vertices[i]`` to ``vertices[i + 1]``.
+        normals: Vec<Vector2f>,
+    },
+}
+
+impl WorldShape {
+    /// Create a polygon, computing its outward edge normals.
+    /// Fewer than three vertices make a degenerate polygon.
+    pub fn polygon(vertices: Vec<Vector2f>) -> WorldShape {
+        let centroid = vertices.iter().fold(Vector2f::new(0., 0.), |acc, &v| acc + v)
+            / vertices.len() as f32;
+
+        let normals = (0..vertices.len()).map(|i| {
+            let v1 = vertices[i];
+            let v2 = vertices[(i + 1) % vertices.len()];
+            let normal = -(v2 - v1).perp().normalize();
+
+            // Winding order isn't fixed, so point each normal away from the center.
+            if normal.dot(&(v1 - centroid)) < 0. {
+                -normal
+            } else {
+                normal
+            }
+        }).collect();
+
+        WorldShape::Polygon { vertices, normals }
+    }
+
+    /// Test whether the shape is a polygon with fewer than three vertices.
+    /// Degenerate shapes have no area, so they never collide, contain a point
+    /// or stop a ray.
+    pub fn is_degenerate(&self) -> bool {
+        match *self {
+            WorldShape::Circle { .. } => false,
+            WorldShape::Polygon { ref vertices, ref normals } => {
+                vertices.len() < 3 || normals.len() != vertices.len()
+            },
+        }
+    }
+
+    /// The center of the shape. A polygon with no vertices is centered on the origin.
+    pub fn center(&self) -> Vector2f {
+        match *self {
+            WorldShape::Circle { center, .. } => center,
+            WorldShape::Polygon { ref vertices, .. } if vertices.is_empty() => {
+                Vector2f::new(0., 0.)
+            },
+            WorldShape::Polygon { ref vertices, .. } => {
+                vertices.iter().fold(Vector2f::new(0., 0.), |acc, &v| acc + v)
+                    / vertices.len() as f32
+            },
+        }
+    }
+
+    /// The radius of the largest circle around ``center`` that fits inside the shape.
+    pub fn inner_radius(&self) -> f32 {
+        match *self {
+            WorldShape::Circle { radius, .. } => radius,
+            WorldShape::Polygon { .. } if self.is_degenerate() => 0.,
+            WorldShape::Polygon { ref vertices, ref normals } => {
+                let center = self.center();
+                vertices.iter()
+                    .zip(normals)
+                    .map(|(&v, n)| n.dot(&(v - center)))
+                    .fold(f32::INFINITY, f32::min)
+            },
+        }
+    }
+
+    /// The axis-aligned bounding box of the shape. A polygon with no vertices
+    /// has inside-out bounds, which intersect and contain nothing.
+    pub fn bounds(&self) -> Bounds {
+        match *self {
+            WorldShape::Circle { center, radius } => {
+                Bounds::new(center - Vector2f::new(radius, radius),
+                            center + Vector2f::new(radius, radius))
+            },
+            WorldShape::Polygon { ref vertices, .. } => {
+                let mut bounds = Bounds::new(Vector2f::new(f32::INFINITY, f32::INFINITY),
+                                             Vector2f::new(f32::NEG_INFINITY, f32::NEG_INFINITY));
+                for &v in vertices {
+                    bounds.min.x = bounds.min.x.min(v.x);
+                    bounds.min.y = bounds.min.y.min(v.y);
+                    bounds.max.x = bounds.max.x.max(v.x);
+                    bounds.max.y = bounds.max.y.max(v.y);
+                }
+                bounds
+            },
+        }
+    }
+
+    /// Test whether a point is inside the shape.
+    pub fn contains(&self, point: Vector2f) -> bool {
+        match *self {
+            WorldShape::Circle { center, radius } => (point - center).length_sq() <= radius * radius,
+            WorldShape::Polygon { .. } if self.is_degenerate() => false,
+            WorldShape::Polygon { ref vertices, ref normals } => {
+                vertices.iter().zip(normals).all(|(&v, n)| n.dot(&(point - v)) <= 0.)
+            },
+        }
+    }
+}
+
+/// An axis-aligned rectangle in world coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    /// The corner with the smallest coordinates.
+    pub min: Vector2f,
+
+    /// The corner with the largest coordinates.
+    pub max: Vector2f,
+}
+
+impl Bounds {
+    /// Create bounds from two corners.
+    pub fn new(min: Vector2f, max: Vector2f) -> Bounds {
+        Bounds { min, max }
+    }
+
+    /// Test whether two bounds overlap.
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        self.min.x <= other.max.x && self.max.x >= other.min.x &&
+            self.min.y <= other.max.y && self.max.y >= other.min.y
+    }
+
+    /// Test whether a point lies within the bounds.
+    pub fn contains(&self, point: Vector2f) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x &&
+            point.y >= self.min.y && point.y <= self.max.y
+    }
+
+    /// The smallest bounds containing both ``self`` and ``other``.
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds::new(Vector2f::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+                    Vector2f::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)))
+    }
+
+    /// Grow the bounds outward by ``margin`` on every side.
+    pub fn expand(&self, margin: f32) -> Bounds {
+        let margin = Vector2f::new(margin, margin);
+        Bounds::new(self.min - margin, self.max + margin)
+    }
+}
+
+fn box_corners(half_extents: Vector2f) -> [Vector2f; 4] {
+    let h = half_extents;
+    [
+        Vector2f::new(-h.x, -h.y),
+        Vector2f::new(h.x, -h.y),
+        Vector2f::new(h.x, h.y),
+        Vector2f::new(-h.x, h.y),
+    ]
+}
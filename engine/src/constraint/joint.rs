@@ -0,0 +1,369 @@
+//! The individual kinds of joint and how each one is enforced.
+
+use std::hash::Hasher;
+use sfml::system::Vector2f;
+use checksum::{SimState, StateHasher};
+use collision::response::inv_effective_mass;
+use entity::EntityPhysics;
+use util::{self, Vector2Ext};
+
+// Stretch a rope or rod is allowed before position correction kicks in.
+const LINEAR_SLOP: f32 = 0.1;
+
+// The most a joint may move a body in one position iteration, so a badly
+// violated joint eases back together instead of snapping.
+const MAX_CORRECTION: f32 = 16.;
+
+/// What a ``Joint`` holds together, and how.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JointKind {
+    /// Keeps the anchors ``length`` apart. A rope only stops them getting
+    /// further apart and goes slack otherwise; a rod also stops them getting closer.
+    Distance {
+        /// The distance to keep between the anchors.
+        length: f32,
+
+        /// Whether the joint is a rope rather than a rod.
+        rope: bool,
+    },
+
+    /// Pulls the anchors towards ``rest_length`` apart with a damped spring.
+    /// Springs are soft, so they stretch under load instead of holding exactly.
+    Spring {
+        /// The length at which the spring exerts no force.
+        rest_length: f32,
+
+        /// Force per unit of stretch.
+        stiffness: f32,
+
+        /// Force per unit of stretching speed, which stops the spring oscillating forever.
+        damping: f32,
+    },
+
+    /// Pins the anchors together, leaving the bodies free to spin around the pin.
+    Revolute,
+
+    /// Pins the anchors together and locks the bodies' rotations, so they move as one.
+    Weld {
+        /// Rotation of body B minus rotation of body A to hold, in degrees.
+        reference_angle: f32,
+    },
+}
+
+/// A constraint between two bodies. Bodies are referred to by their index in
+/// the slice given to ``ConstraintSolver::solve``, and anchors are points in
+/// each body's own (rotated) coordinate frame, relative to its center.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Joint {
+    kind: JointKind,
+    body_a: usize,
+    anchor_a: Vector2f,
+    body_b: usize,
+    anchor_b: Vector2f,
+
+    // Strongest force and torque the joint can take before it breaks.
+    break_force: Option<f32>,
+    break_torque: Option<f32>,
+    broken: bool,
+
+    // Impulses applied to body B during the current solve.
+    linear_impulse: Vector2f,
+    angular_impulse: f32,
+
+    // Force and torque on body B over the last solve.
+    reaction_force: Vector2f,
+    reaction_torque: f32,
+}
+
+impl Joint {
+    /// Create an unbreakable joint between two bodies.
+    pub fn new<T1, T2>(kind: JointKind, body_a: usize, anchor_a: T1, body_b: usize, anchor_b: T2)
+                       -> Joint
+        where T1: Into<Vector2f>,
+              T2: Into<Vector2f>
+    {
+        Joint {
+            kind,
+            body_a,
+            anchor_a: anchor_a.into(),
+            body_b,
+            anchor_b: anchor_b.into(),
+            break_force: None,
+            break_torque: None,
+            broken: false,
+            linear_impulse: Vector2f::new(0., 0.),
+            angular_impulse: 0.,
+            reaction_force: Vector2f::new(0., 0.),
+            reaction_torque: 0.,
+        }
+    }
+
+    /// Weld two bodies together exactly as they are now, anchored at the center of ``b``.
+    pub fn weld_in_place(body_a: usize, a: &EntityPhysics, body_b: usize, b: &EntityPhysics) -> Joint {
+        let kind = JointKind::Weld {
+            reference_angle: util::angle_delta(a.rot(), b.rot()),
+        };
+        Joint::new(kind, body_a, a.world_to_local(b.pos()), body_b, (0., 0.))
+    }
+
+    /// Let the joint break when it has to pull or push harder than ``force``.
+    /// ``None`` makes it unbreakable.
+    pub fn set_break_force(&mut self, force: Option<f32>) {
+        self.break_force = force;
+    }
+
+    /// Let a weld break when it has to twist harder than ``torque``.
+    /// ``None`` makes it unbreakable.
+    pub fn set_break_torque(&mut self, torque: Option<f32>) {
+        self.break_torque = torque;
+    }
+
+    /// Change the kind of joint, such as to reel a rope in or out.
+    pub fn set_kind(&mut self, kind: JointKind) {
+        self.kind = kind;
+    }
+
+    /// Return the kind of joint.
+    pub fn kind(&self) -> JointKind {
+        self.kind
+    }
+
+    /// Return the index and local anchor of the first body.
+    pub fn body_a(&self) -> (usize, Vector2f) {
+        (self.body_a, self.anchor_a)
+    }
+
+    /// Return the index and local anchor of the second body.
+    pub fn body_b(&self) -> (usize, Vector2f) {
+        (self.body_b, self.anchor_b)
+    }
+
+    /// Whether the joint has broken. Broken joints are no longer enforced.
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+
+    /// The force the joint exerted on body B during the last solve.
+    /// Body A felt the opposite.
+    pub fn reaction_force(&self) -> Vector2f {
+        self.reaction_force
+    }
+
+    /// The torque the joint exerted on body B during the last solve, not counting
+    /// the torque from ``reaction_force``. Only welds exert one.
+    pub fn reaction_torque(&self) -> f32 {
+        self.reaction_torque
+    }
+
+    // Get ready for a new solve.
+    pub(super) fn begin(&mut self) {
+        self.linear_impulse = Vector2f::new(0., 0.);
+        self.angular_impulse = 0.;
+    }
+
+    // Work out the reaction and check whether the joint broke. Returns true if it just broke.
+    pub(super) fn finish(&mut self, dt: f32) -> bool {
+        self.reaction_force = self.linear_impulse / dt;
+        self.reaction_torque = self.angular_impulse / dt;
+
+        let too_much_force = self.break_force.is_some_and(|max| self.reaction_force.length() > max);
+        let too_much_torque = self.break_torque.is_some_and(|max| self.reaction_torque.abs() > max);
+        self.broken = too_much_force || too_much_torque;
+        self.broken
+    }
+
+    // Springs act once per solve, as a force over the step.
+    pub(super) fn apply_spring(&mut self, a: &mut EntityPhysics, b: &mut EntityPhysics, dt: f32) {
+        let (rest_length, stiffness, damping) = match self.kind {
+            JointKind::Spring { rest_length, stiffness, damping } => (rest_length, stiffness, damping),
+            _ => return,
+        };
+
+        let (ra, rb) = self.world_anchors(a, b);
+        let (pa, pb) = (a.pos() + ra, b.pos() + rb);
+        let (normal, length) = match direction(pb - pa) {
+            Some(direction) => direction,
+            None => return,
+        };
+
+        let stretch_speed = (b.vel_at(pb) - a.vel_at(pa)).dot(&normal);
+        let force = -(stiffness * (length - rest_length) + damping * stretch_speed);
+        self.apply_impulse(a, b, ra, rb, normal * (force * dt));
+    }
+
+    // Remove relative velocity that would break the joint.
+    pub(super) fn solve_velocity(&mut self, a: &mut EntityPhysics, b: &mut EntityPhysics) {
+        let (ra, rb) = self.world_anchors(a, b);
+        let (pa, pb) = (a.pos() + ra, b.pos() + rb);
+
+        match self.kind {
+            JointKind::Distance { length, rope } => {
+                let (normal, current) = match direction(pb - pa) {
+                    Some(direction) => direction,
+                    None => return,
+                };
+
+                let stretch_speed = (b.vel_at(pb) - a.vel_at(pa)).dot(&normal);
+                if rope && (current < length || stretch_speed < 0.) {
+                    // Slack, or already getting shorter.
+                    return;
+                }
+
+                let k = inv_effective_mass(a, b, ra, rb, normal);
+                if k > 0. {
+                    self.apply_impulse(a, b, ra, rb, normal * (-stretch_speed / k));
+                }
+            },
+            JointKind::Spring { .. } => {},
+            JointKind::Revolute => {
+                let impulse = solve_point(a, b, ra, rb, b.vel_at(pb) - a.vel_at(pa));
+                self.apply_impulse(a, b, ra, rb, impulse);
+            },
+            JointKind::Weld { .. } => {
+                let k = a.inv_rotational_inertia() + b.inv_rotational_inertia();
+                if k > 0. {
+                    let spin = b.angular_vel_radians() - a.angular_vel_radians();
+                    let impulse = -spin / k;
+                    a.apply_angular_impulse(-impulse);
+                    b.apply_angular_impulse(impulse);
+                    self.angular_impulse += impulse;
+                }
+
+                let impulse = solve_point(a, b, ra, rb, b.vel_at(pb) - a.vel_at(pa));
+                self.apply_impulse(a, b, ra, rb, impulse);
+            },
+        }
+    }
+
+    // Move the bodies directly to remove drift that velocities alone leave behind.
+    pub(super) fn solve_position(&self, a: &mut EntityPhysics, b: &mut EntityPhysics) {
+        if let JointKind::Weld { reference_angle } = self.kind {
+            let k = a.inv_rotational_inertia() + b.inv_rotational_inertia();
+            if k > 0. {
+                let error = util::angle_delta(a.rot() + reference_angle, b.rot());
+                let correction = -error / k;
+                a.turn(-correction * a.inv_rotational_inertia());
+                b.turn(correction * b.inv_rotational_inertia());
+            }
+        }
+
+        let (ra, rb) = self.world_anchors(a, b);
+        let separation = (b.pos() + rb) - (a.pos() + ra);
+
+        let correction = match self.kind {
+            JointKind::Distance { length, rope } => {
+                let (normal, current) = match direction(separation) {
+                    Some(direction) => direction,
+                    None => return,
+                };
+
+                let error = current - length;
+                if error.abs() < LINEAR_SLOP || (rope && error < 0.) {
+                    return;
+                }
+
+                let k = inv_effective_mass(a, b, ra, rb, normal);
+                if k <= 0. {
+                    return;
+                }
+                normal * (-clamp_correction(error) / k)
+            },
+            JointKind::Spring { .. } => return,
+            JointKind::Revolute | JointKind::Weld { .. } => {
+                let length = separation.length();
+                if length < LINEAR_SLOP {
+                    return;
+                }
+                solve_point(a, b, ra, rb, separation * (clamp_correction(length) / length))
+            },
+        };
+
+        a.translate(correction * -a.inv_mass());
+        a.turn((ra.cross(&correction) * -a.inv_rotational_inertia()).to_degrees());
+        b.translate(correction * b.inv_mass());
+        b.turn((rb.cross(&correction) * b.inv_rotational_inertia()).to_degrees());
+    }
+
+    fn world_anchors(&self, a: &EntityPhysics, b: &EntityPhysics) -> (Vector2f, Vector2f) {
+        (a.local_to_world_vector(self.anchor_a), b.local_to_world_vector(self.anchor_b))
+    }
+
+    // Apply ``impulse`` to B at its anchor, and the opposite to A.
+    fn apply_impulse(&mut self, a: &mut EntityPhysics, b: &mut EntityPhysics,
+                     ra: Vector2f, rb: Vector2f, impulse: Vector2f) {
+        let (pa, pb) = (a.pos() + ra, b.pos() + rb);
+        a.apply_impulse_at(-impulse, pa);
+        b.apply_impulse_at(impulse, pb);
+        self.linear_impulse += impulse;
+    }
+}
+
+// Split a vector into its direction and length, unless it's too short to have a direction.
+fn direction(v: Vector2f) -> Option<(Vector2f, f32)> {
+    let length = v.length();
+    if length > f32::EPSILON {
+        Some((v / length, length))
+    } else {
+        None
+    }
+}
+
+fn clamp_correction(error: f32) -> f32 {
+    error.clamp(-MAX_CORRECTION, MAX_CORRECTION)
+}
+
+// The impulse that cancels ``error`` between two anchors, which must stay together.
+// Solves the 2x2 system ``K impulse = -error``, where ``K`` is the effective mass matrix.
+fn solve_point(a: &EntityPhysics, b: &EntityPhysics, ra: Vector2f, rb: Vector2f,
+               error: Vector2f) -> Vector2f {
+    let inv_mass = a.inv_mass() + b.inv_mass();
+    let (ia, ib) = (a.inv_rotational_inertia(), b.inv_rotational_inertia());
+
+    let k11 = inv_mass + ia * ra.y * ra.y + ib * rb.y * rb.y;
+    let k12 = -ia * ra.x * ra.y - ib * rb.x * rb.y;
+    let k22 = inv_mass + ia * ra.x * ra.x + ib * rb.x * rb.x;
+
+    // Relative to the diagonal, so heavy bodies with tiny inverse masses still solve.
+    let det = k11 * k22 - k12 * k12;
+    if det.abs() <= k11 * k22 * f32::EPSILON {
+        return Vector2f::new(0., 0.);
+    }
+
+    Vector2f::new(-(k22 * error.x - k12 * error.y) / det,
+                  -(k11 * error.y - k12 * error.x) / det)
+}
+
+impl SimState for Joint {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        // Kinds can change at runtime, such as a rope being reeled in.
+        match self.kind {
+            JointKind::Distance { length, rope } => {
+                hasher.write_u8(0);
+                hasher.write_f32(length);
+                hasher.write_bool(rope);
+            },
+            JointKind::Spring { rest_length, stiffness, damping } => {
+                hasher.write_u8(1);
+                hasher.write_f32(rest_length);
+                hasher.write_f32(stiffness);
+                hasher.write_f32(damping);
+            },
+            JointKind::Revolute => hasher.write_u8(2),
+            JointKind::Weld { reference_angle } => {
+                hasher.write_u8(3);
+                hasher.write_f32(reference_angle);
+            },
+        }
+
+        hasher.write_usize(self.body_a);
+        hasher.write_vector(self.anchor_a);
+        hasher.write_usize(self.body_b);
+        hasher.write_vector(self.anchor_b);
+
+        for limit in &[self.break_force, self.break_torque] {
+            hasher.write_bool(limit.is_some());
+            hasher.write_f32(limit.unwrap_or(0.));
+        }
+        hasher.write_bool(self.broken);
+    }
+}
@@ -0,0 +1,181 @@
+//! Joints that hold bodies together: ropes and rods, springs, pins and welds.
+//!
+//! A ``ConstraintSolver`` owns a set of ``Joint``s and enforces them once per
+//! tick, after the bodies have been updated and collisions resolved. Velocities
+//! are fixed first by applying impulses at the anchors, repeated a number of
+//! iterations so joints that share a body settle together. Then positions are
+//! nudged to remove the drift left over. More iterations give stiffer chains at
+//! the cost of speed.
+//!
+//! To join entities in a ``World``, step the solver with ``World::solve_constraints``.
+
+pub use self::joint::{Joint, JointKind};
+
+pub mod joint;
+
+use std::hash::Hasher;
+use checksum::{SimState, StateHasher};
+use entity::EntityPhysics;
+
+/// Velocity iterations used unless told otherwise.
+pub const DEFAULT_ITERATIONS: u32 = 8;
+
+/// Position iterations used unless told otherwise.
+pub const DEFAULT_POSITION_ITERATIONS: u32 = 3;
+
+/// Enforces a set of joints. See the module documentation.
+#[derive(Clone, Debug)]
+pub struct ConstraintSolver {
+    joints: Vec<Option<Joint>>,
+    iterations: u32,
+    position_iterations: u32,
+}
+
+impl ConstraintSolver {
+    /// Create a solver with no joints and the default iteration counts.
+    pub fn new() -> ConstraintSolver {
+        ConstraintSolver {
+            joints: Vec::new(),
+            iterations: DEFAULT_ITERATIONS,
+            position_iterations: DEFAULT_POSITION_ITERATIONS,
+        }
+    }
+
+    /// Add a joint, returning an id for it. Ids are never reused.
+    /// Fails if the joint connects a body to itself.
+    pub fn add(&mut self, joint: Joint) -> Result<usize, String> {
+        if joint.body_a().0 == joint.body_b().0 {
+            return Err(format!("a joint can't connect body {} to itself", joint.body_a().0));
+        }
+
+        self.joints.push(Some(joint));
+        Ok(self.joints.len() - 1)
+    }
+
+    /// Remove a joint, returning it if it was there.
+    pub fn remove(&mut self, id: usize) -> Option<Joint> {
+        self.joints.get_mut(id).and_then(Option::take)
+    }
+
+    /// Access a joint by id.
+    pub fn joint(&self, id: usize) -> Option<&Joint> {
+        self.joints.get(id).and_then(Option::as_ref)
+    }
+
+    /// Mutably access a joint by id.
+    pub fn joint_mut(&mut self, id: usize) -> Option<&mut Joint> {
+        self.joints.get_mut(id).and_then(Option::as_mut)
+    }
+
+    /// Iterate over every joint along with its id, including broken ones.
+    pub fn joints(&self) -> impl Iterator<Item = (usize, &Joint)> {
+        self.joints.iter()
+            .enumerate()
+            .filter_map(|(id, joint)| joint.as_ref().map(|joint| (id, joint)))
+    }
+
+    /// Set how many times per solve velocities are corrected.
+    pub fn set_iterations(&mut self, iterations: u32) {
+        self.iterations = iterations;
+    }
+
+    /// Set how many times per solve positions are corrected.
+    pub fn set_position_iterations(&mut self, position_iterations: u32) {
+        self.position_iterations = position_iterations;
+    }
+
+    /// Return how many times per solve velocities are corrected.
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    /// Return how many times per solve positions are corrected.
+    pub fn position_iterations(&self) -> u32 {
+        self.position_iterations
+    }
+
+    /// Enforce every joint over a tick of ``dt`` seconds. Joints refer to
+    /// bodies by their index in ``bodies``. Returns the ids of joints that
+    /// broke during this solve; they stay in the solver, but are ignored.
+    /// Fails without moving anything if a joint refers to a body that isn't there.
+    pub fn solve(&mut self, bodies: &mut [&mut EntityPhysics], dt: f32)
+                 -> Result<Vec<usize>, String> {
+        for (id, joint) in self.joints() {
+            for &(body, _) in &[joint.body_a(), joint.body_b()] {
+                if body >= bodies.len() {
+                    return Err(format!("joint {}: no body {}", id, body));
+                }
+            }
+        }
+
+        for joint in self.active_joints() {
+            joint.begin();
+            let (a, b) = pair(bodies, joint.body_a().0, joint.body_b().0);
+            joint.apply_spring(a, b, dt);
+        }
+
+        for _ in 0..self.iterations {
+            for joint in self.active_joints() {
+                let (a, b) = pair(bodies, joint.body_a().0, joint.body_b().0);
+                joint.solve_velocity(a, b);
+            }
+        }
+
+        let mut broken = Vec::new();
+        for (id, joint) in self.joints.iter_mut().enumerate() {
+            if let Some(ref mut joint) = *joint {
+                if !joint.is_broken() && joint.finish(dt) {
+                    broken.push(id);
+                }
+            }
+        }
+
+        for _ in 0..self.position_iterations {
+            for joint in self.active_joints() {
+                let (a, b) = pair(bodies, joint.body_a().0, joint.body_b().0);
+                joint.solve_position(a, b);
+            }
+        }
+
+        Ok(broken)
+    }
+
+    fn active_joints(&mut self) -> impl Iterator<Item = &mut Joint> {
+        self.joints.iter_mut()
+            .filter_map(Option::as_mut)
+            .filter(|joint| !joint.is_broken())
+    }
+}
+
+impl Default for ConstraintSolver {
+    fn default() -> ConstraintSolver {
+        ConstraintSolver::new()
+    }
+}
+
+// Borrow the two bodies of a joint at once. ``add`` and ``solve`` have already
+// checked that they are different and exist.
+fn pair<'a>(bodies: &'a mut [&mut EntityPhysics], a: usize, b: usize)
+            -> (&'a mut EntityPhysics, &'a mut EntityPhysics) {
+    debug_assert!(a != b, "a joint can't connect a body to itself");
+
+    if a < b {
+        let (left, right) = bodies.split_at_mut(b);
+        (&mut *left[a], &mut *right[0])
+    } else {
+        let (left, right) = bodies.split_at_mut(a);
+        (&mut *right[0], &mut *left[b])
+    }
+}
+
+impl SimState for ConstraintSolver {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u32(self.iterations);
+        hasher.write_u32(self.position_iterations);
+        hasher.write_usize(self.joints().count());
+        for (id, joint) in self.joints() {
+            hasher.write_usize(id);
+            joint.hash_state(hasher);
+        }
+    }
+}
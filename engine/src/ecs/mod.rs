@@ -0,0 +1,299 @@
+//! Entities built out of components, as an alternative to writing a struct
+//! that implements ``Entity`` for every kind of object.
+//!
+//! A ``Registry`` hands out ``Handle``s for entities, which are nothing but
+//! ids. Data lives in one ``Storage`` per component type: ``EntityPhysics``,
+//! ``RcSprite``, ``Collider``, ``Thrusters``, or any gameplay type, such as
+//! hit points or a cargo hold. Systems are functions that run over the
+//! storages they care about, usually by joining one storage with another.
+//! The ``systems`` module has the ones that move, collide and draw things.
+//!
+//! Storages are borrowed separately, so a system can read colliders while it
+//! writes physics. Borrowing the same storage mutably twice at once panics.
+//! A ``SpriteEntity`` can still be built as before and spawned as a bundle of
+//! its parts.
+
+pub use self::storage::Storage;
+
+pub mod storage;
+pub mod systems;
+
+use std::any::TypeId;
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use checksum::{SimState, StateHasher};
+use collision::Collider;
+use entity::{Entity, EntityPhysics, ForceField, SpriteEntity, Thrusters};
+use refcounted::RcSprite;
+use world::{Allocator, Handle};
+use self::storage::AnyStorage;
+
+/// Owns entity ids and a storage for each component type.
+/// See the module documentation.
+pub struct Registry {
+    allocator: Allocator,
+    alive: Vec<bool>,
+    len: usize,
+    storages: HashMap<TypeId, Box<dyn AnyStorage>>,
+}
+
+impl Registry {
+    /// Create an empty registry, with storages for the engine's own components
+    /// already registered.
+    pub fn new() -> Registry {
+        let mut registry = Registry {
+            allocator: Allocator::default(),
+            alive: Vec::new(),
+            len: 0,
+            storages: HashMap::new(),
+        };
+
+        registry.register::<EntityPhysics>();
+        registry.register::<RcSprite>();
+        registry.register::<Collider>();
+        registry.register::<Thrusters>();
+        registry
+    }
+
+    /// Add a storage for a component type, if there isn't one yet.
+    /// Inserting a component registers its type too.
+    pub fn register<T: 'static>(&mut self) {
+        self.storages.entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(RefCell::new(Storage::<T>::new())));
+    }
+
+    /// Create an entity with no components.
+    pub fn spawn(&mut self) -> Handle {
+        let entity = self.allocator.allocate();
+        let index = entity.index() as usize;
+        if index >= self.alive.len() {
+            self.alive.resize(index + 1, false);
+        }
+
+        self.alive[index] = true;
+        self.len += 1;
+        entity
+    }
+
+    /// Create an entity from a bundle of components.
+    pub fn spawn_bundle<B: Bundle>(&mut self, bundle: B) -> Handle {
+        let entity = self.spawn();
+        bundle.insert_into(self, entity);
+        entity
+    }
+
+    /// Destroy an entity and all of its components.
+    /// Returns ``false`` if it was already gone.
+    pub fn despawn(&mut self, entity: Handle) -> bool {
+        if !self.contains(entity) {
+            return false;
+        }
+
+        for storage in self.storages.values_mut() {
+            storage.remove_entity(entity);
+        }
+
+        self.alive[entity.index() as usize] = false;
+        self.allocator.release(entity);
+        self.len -= 1;
+        true
+    }
+
+    /// Test whether an entity is alive.
+    pub fn contains(&self, entity: Handle) -> bool {
+        self.allocator.is_current(entity) && self.alive[entity.index() as usize]
+    }
+
+    /// Return the number of live entities.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return whether there are no live entities.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over every live entity.
+    pub fn entities(&self) -> impl Iterator<Item = Handle> + '_ {
+        self.alive.iter()
+            .enumerate()
+            .filter(|&(_, &alive)| alive)
+            .map(move |(index, _)| self.allocator.handle(index))
+    }
+
+    /// Give an entity a component, returning the one it replaced.
+    /// Panics if the entity is dead.
+    pub fn insert<T: 'static>(&mut self, entity: Handle, component: T) -> Option<T> {
+        assert!(self.contains(entity), "can't add a component to a dead entity");
+        self.register::<T>();
+        self.storage_mut::<T>().insert(entity, component)
+    }
+
+    /// Take a component away from an entity, returning it.
+    pub fn remove<T: 'static>(&mut self, entity: Handle) -> Option<T> {
+        self.try_storage_mut::<T>()?.remove(entity)
+    }
+
+    /// Test whether an entity has a component.
+    pub fn has<T: 'static>(&self, entity: Handle) -> bool {
+        self.try_storage::<T>().is_some_and(|storage| storage.contains(entity))
+    }
+
+    /// Borrow the storage for a component type.
+    /// Panics if the type was never registered, or if it is borrowed mutably.
+    pub fn storage<T: 'static>(&self) -> Ref<'_, Storage<T>> {
+        self.try_storage().expect("component type was never registered")
+    }
+
+    /// Mutably borrow the storage for a component type.
+    /// Panics if the type was never registered, or if it is borrowed at all.
+    pub fn storage_mut<T: 'static>(&self) -> RefMut<'_, Storage<T>> {
+        self.try_storage_mut().expect("component type was never registered")
+    }
+
+    /// Borrow the storage for a component type, if it is registered.
+    pub fn try_storage<T: 'static>(&self) -> Option<Ref<'_, Storage<T>>> {
+        self.cell::<T>().map(RefCell::borrow)
+    }
+
+    /// Mutably borrow the storage for a component type, if it is registered.
+    pub fn try_storage_mut<T: 'static>(&self) -> Option<RefMut<'_, Storage<T>>> {
+        self.cell::<T>().map(RefCell::borrow_mut)
+    }
+
+    fn cell<T: 'static>(&self) -> Option<&RefCell<Storage<T>>> {
+        self.storages.get(&TypeId::of::<T>())
+            .map(|storage| storage.as_any().downcast_ref().expect("storage has the wrong type"))
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        Registry::new()
+    }
+}
+
+/// A group of components added to an entity together.
+pub trait Bundle {
+    /// Add every component to ``entity``.
+    fn insert_into(self, registry: &mut Registry, entity: Handle);
+}
+
+impl Bundle for SpriteEntity {
+    fn insert_into(self, registry: &mut Registry, entity: Handle) {
+        let (sprite, phys, collider, thrusters) = self.into_parts();
+        registry.insert(entity, sprite);
+        registry.insert(entity, phys);
+        if let Some(collider) = collider {
+            registry.insert(entity, collider);
+        }
+        if let Some(thrusters) = thrusters {
+            registry.insert(entity, thrusters);
+        }
+    }
+}
+
+macro_rules! tuple_bundle {
+    ($($name:ident),+) => {
+        impl<$($name: 'static),+> Bundle for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn insert_into(self, registry: &mut Registry, entity: Handle) {
+                let ($($name,)+) = self;
+                $(registry.insert(entity, $name);)+
+            }
+        }
+    };
+}
+
+tuple_bundle!(A);
+tuple_bundle!(A, B);
+tuple_bundle!(A, B, C);
+tuple_bundle!(A, B, C, D);
+tuple_bundle!(A, B, C, D, E);
+tuple_bundle!(A, B, C, D, E, F);
+
+/// Something run over a registry every tick. Systems also get a context for
+/// whatever lives outside the registry, which for the engine's own systems is
+/// the ``PlanetManager``.
+/// Implemented for every ``FnMut(&mut Registry, &mut C, f32)``, which gets ``dt``.
+pub trait System<C: ?Sized> {
+    /// Run the system over a tick of ``dt`` seconds.
+    fn run(&mut self, registry: &mut Registry, context: &mut C, dt: f32);
+}
+
+impl<C: ?Sized, F: FnMut(&mut Registry, &mut C, f32)> System<C> for F {
+    fn run(&mut self, registry: &mut Registry, context: &mut C, dt: f32) {
+        self(registry, context, dt)
+    }
+}
+
+/// Systems run one after another, in the order they were added.
+pub struct Schedule<C: ?Sized> {
+    systems: Vec<Box<dyn System<C>>>,
+}
+
+impl<C: ?Sized> Schedule<C> {
+    /// Create a schedule with no systems.
+    pub fn new() -> Schedule<C> {
+        Schedule {
+            systems: Vec::new(),
+        }
+    }
+
+    /// Add a system to run after the ones already added.
+    pub fn add<S: System<C> + 'static>(&mut self, system: S) {
+        self.systems.push(Box::new(system));
+    }
+
+    /// Run every system over a tick of ``dt`` seconds.
+    pub fn run(&mut self, registry: &mut Registry, context: &mut C, dt: f32) {
+        for system in &mut self.systems {
+            system.run(registry, context, dt);
+        }
+    }
+}
+
+impl<C: ?Sized> Default for Schedule<C> {
+    fn default() -> Schedule<C> {
+        Schedule::new()
+    }
+}
+
+/// The physics and collider of a component-based entity, borrowed together so
+/// they can be passed to anything that takes an ``Entity``, such as
+/// ``PlanetManager::collide_entity``.
+pub struct Parts<'a> {
+    /// The entity's physics.
+    pub phys: &'a mut EntityPhysics,
+
+    /// The entity's collider, if it has one.
+    pub collider: Option<&'a Collider>,
+}
+
+impl<'a> Entity for Parts<'a> {
+    fn update(&mut self, dt: f32, field: &dyn ForceField) {
+        self.phys.update_in(dt, field);
+    }
+
+    fn phys(&self) -> &EntityPhysics {
+        self.phys
+    }
+
+    fn phys_mut(&mut self) -> &mut EntityPhysics {
+        self.phys
+    }
+
+    fn collider(&self) -> Option<&Collider> {
+        self.collider
+    }
+}
+
+impl SimState for Registry {
+    /// Hashes the physics and thrusters of every entity. Gameplay components
+    /// can be covered by hashing their storages too.
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        self.storage::<EntityPhysics>().hash_state(hasher);
+        self.storage::<Thrusters>().hash_state(hasher);
+    }
+}
@@ -0,0 +1,171 @@
+//! Storage for one type of component.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::hash::Hasher;
+use sfml::graphics::{Drawable, RenderStates, RenderTarget};
+use checksum::{SimState, StateHasher};
+use refcounted::RcSprite;
+use world::Handle;
+
+/// Every component of type ``T``, indexed by entity.
+/// Iteration visits entities in order of their slot, so systems are deterministic.
+#[derive(Debug)]
+pub struct Storage<T> {
+    slots: Vec<Option<(Handle, T)>>,
+    len: usize,
+}
+
+impl<T> Storage<T> {
+    /// Create an empty storage.
+    pub fn new() -> Storage<T> {
+        Storage {
+            slots: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Give an entity a component, returning the one it replaced.
+    pub fn insert(&mut self, entity: Handle, component: T) -> Option<T> {
+        let index = entity.index() as usize;
+        if index >= self.slots.len() {
+            self.slots.resize_with(index + 1, || None);
+        }
+
+        let old = self.slots[index].replace((entity, component));
+        match old {
+            // A component left behind by an older entity in the same slot doesn't count.
+            Some((owner, old)) if owner == entity => Some(old),
+            Some(_) => None,
+            None => {
+                self.len += 1;
+                None
+            },
+        }
+    }
+
+    /// Take a component away from an entity, returning it.
+    pub fn remove(&mut self, entity: Handle) -> Option<T> {
+        if !self.contains(entity) {
+            return None;
+        }
+
+        self.len -= 1;
+        self.slots[entity.index() as usize].take().map(|(_, component)| component)
+    }
+
+    /// Test whether an entity has this component.
+    pub fn contains(&self, entity: Handle) -> bool {
+        self.get(entity).is_some()
+    }
+
+    /// Access an entity's component.
+    pub fn get(&self, entity: Handle) -> Option<&T> {
+        match self.slots.get(entity.index() as usize) {
+            Some(&Some((owner, ref component))) if owner == entity => Some(component),
+            _ => None,
+        }
+    }
+
+    /// Mutably access an entity's component.
+    pub fn get_mut(&mut self, entity: Handle) -> Option<&mut T> {
+        match self.slots.get_mut(entity.index() as usize) {
+            Some(&mut Some((owner, ref mut component))) if owner == entity => Some(component),
+            _ => None,
+        }
+    }
+
+    /// Return the number of entities with this component.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return whether no entity has this component.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over every component along with its entity.
+    pub fn iter(&self) -> impl Iterator<Item = (Handle, &T)> {
+        self.slots.iter()
+            .flatten()
+            .map(|&(entity, ref component)| (entity, component))
+    }
+
+    /// Mutably iterate over every component along with its entity.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Handle, &mut T)> {
+        self.slots.iter_mut()
+            .flatten()
+            .map(|&mut (entity, ref mut component)| (entity, component))
+    }
+
+    /// Iterate over the entities that have both this component and one in ``other``.
+    pub fn join<'a, U>(&'a self, other: &'a Storage<U>)
+                       -> impl Iterator<Item = (Handle, &'a T, &'a U)> {
+        self.iter()
+            .filter_map(move |(entity, component)| {
+                other.get(entity).map(|other| (entity, component, other))
+            })
+    }
+
+    /// Like ``join``, but with this component mutable.
+    pub fn join_mut<'a, U>(&'a mut self, other: &'a Storage<U>)
+                           -> impl Iterator<Item = (Handle, &'a mut T, &'a U)> {
+        self.iter_mut()
+            .filter_map(move |(entity, component)| {
+                other.get(entity).map(|other| (entity, component, other))
+            })
+    }
+}
+
+impl<T> Default for Storage<T> {
+    fn default() -> Storage<T> {
+        Storage::new()
+    }
+}
+
+/// Sprites are drawn in order of their entity's slot.
+impl Drawable for Storage<RcSprite> {
+    fn draw<'a: 'shader, 'texture, 'shader, 'shader_texture> (
+            &'a self,
+            target: &mut dyn RenderTarget,
+            states: RenderStates<'texture, 'shader, 'shader_texture>) {
+        for (_, sprite) in self.iter() {
+            let states = RenderStates {
+                blend_mode: states.blend_mode,
+                transform: states.transform,
+                texture: states.texture,
+                shader: states.shader,
+            };
+
+            sprite.draw(target, states);
+        }
+    }
+}
+
+// A storage of any component type, so a registry can keep them all together.
+pub(super) trait AnyStorage {
+    fn remove_entity(&mut self, entity: Handle);
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: 'static> AnyStorage for RefCell<Storage<T>> {
+    fn remove_entity(&mut self, entity: Handle) {
+        self.get_mut().remove(entity);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<T: SimState> SimState for Storage<T> {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.len());
+        for (entity, component) in self.iter() {
+            entity.hash_state(hasher);
+            component.hash_state(hasher);
+        }
+    }
+}
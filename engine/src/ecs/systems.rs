@@ -0,0 +1,103 @@
+//! Systems for the engine's own components. A tick usually runs
+//! ``fire_thrusters``, ``drag``, ``physics``, ``collide_planets`` and
+//! ``collide_entities`` in that order, all of which fit a
+//! ``Schedule<PlanetManager>``, then each frame ``sync_sprites`` before drawing
+//! the ``RcSprite`` storage.
+
+use sfml::graphics::Transformable;
+use collision::{self, Collider};
+use entity::{Entity, EntityPhysics, ForceField, Thrusters};
+use planet_manager::PlanetManager;
+use refcounted::RcSprite;
+use super::{Parts, Registry};
+
+/// Fire the thrusters of every entity that has both thrusters and physics.
+pub fn fire_thrusters<C: ?Sized>(registry: &mut Registry, _context: &mut C, dt: f32) {
+    let mut thrusters = registry.storage_mut::<Thrusters>();
+    let mut phys = registry.storage_mut::<EntityPhysics>();
+
+    for (entity, thrusters) in thrusters.iter_mut() {
+        if let Some(phys) = phys.get_mut(entity) {
+            thrusters.fire(phys, dt);
+        }
+    }
+}
+
+/// Advance every entity's physics by ``dt`` seconds under the influence of ``field``.
+pub fn physics<F: ForceField>(registry: &mut Registry, field: &mut F, dt: f32) {
+    for (_, phys) in registry.storage_mut::<EntityPhysics>().iter_mut() {
+        phys.update_in(dt, &*field);
+    }
+}
+
+/// Apply the gravity of planets to every entity, sampled once at its current
+/// position. See ``PlanetManager::affect_entity``.
+pub fn gravity(registry: &mut Registry, planets: &mut PlanetManager, _dt: f32) {
+    for (_, phys) in registry.storage_mut::<EntityPhysics>().iter_mut() {
+        let force = planets.force_at(phys.pos(), phys.mass());
+        phys.apply_force(force);
+    }
+}
+
+/// Slow down and heat up every entity flying through an atmosphere.
+/// See ``PlanetManager::apply_atmosphere``.
+pub fn drag(registry: &mut Registry, planets: &mut PlanetManager, dt: f32) {
+    for (_, phys) in registry.storage_mut::<EntityPhysics>().iter_mut() {
+        planets.apply_atmosphere(phys, dt);
+    }
+}
+
+/// Bounce every entity with a collider off the planets it touches.
+/// See ``PlanetManager::collide_entity``.
+pub fn collide_planets(registry: &mut Registry, planets: &mut PlanetManager, _dt: f32) {
+    let mut phys = registry.storage_mut::<EntityPhysics>();
+    let colliders = registry.storage::<Collider>();
+
+    for (_, phys, collider) in phys.join_mut(&colliders) {
+        planets.collide_entity(&mut Parts {
+            phys,
+            collider: Some(collider),
+        });
+    }
+}
+
+/// Bounce entities with colliders off each other. See ``collision::collide_all``.
+pub fn collide_entities<C: ?Sized>(registry: &mut Registry, _context: &mut C, _dt: f32) {
+    let mut phys = registry.storage_mut::<EntityPhysics>();
+    let colliders = registry.storage::<Collider>();
+
+    let mut parts: Vec<Parts> = phys.join_mut(&colliders)
+        .map(|(_, phys, collider)| Parts {
+            phys,
+            collider: Some(collider),
+        })
+        .collect();
+    let mut parts: Vec<&mut Parts> = parts.iter_mut().collect();
+    collision::collide_all(&mut parts);
+}
+
+/// Bounce an entity kept outside the registry, such as one in a ``World``,
+/// off every entity in it with a collider. See ``collision::collide_entities``.
+pub fn collide_entity<E: Entity + ?Sized>(registry: &Registry, entity: &mut E) {
+    let mut phys = registry.storage_mut::<EntityPhysics>();
+    let colliders = registry.storage::<Collider>();
+
+    for (_, phys, collider) in phys.join_mut(&colliders) {
+        collision::collide_entities(entity, &mut Parts {
+            phys,
+            collider: Some(collider),
+        });
+    }
+}
+
+/// Move every sprite to its entity's physics transform blended between the
+/// previous and current tick. Call this once per frame, before drawing.
+pub fn sync_sprites(registry: &Registry, alpha: f32) {
+    let mut sprites = registry.storage_mut::<RcSprite>();
+    let phys = registry.storage::<EntityPhysics>();
+
+    for (_, sprite, phys) in sprites.join_mut(&phys) {
+        sprite.set_position(phys.interpolated_pos(alpha));
+        sprite.set_rotation(phys.interpolated_rot(alpha));
+    }
+}
@@ -1,48 +1,80 @@
 //! Managing and defining generic game objects.
 
+pub use self::integrator::{ForceField, Integrator, NoField};
 pub use self::sprite_entity::SpriteEntity;
+pub use self::thruster::{Thruster, Thrusters};
 
+pub mod integrator;
 pub mod sprite_entity;
+pub mod thruster;
 
 pub const TICKS_SEC: u32 = 45;
 
+/// The fraction of heat an entity sheds per second unless told otherwise.
+pub const DEFAULT_COOLING: f32 = 0.5;
+
+use std::hash::Hasher;
 use sfml::system::Vector2f;
+use checksum::{SimState, StateHasher};
+use collision::Collider;
+use util::{self, Vector2Ext};
 
 /// The transformation on a entity, as well as it's velocity.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct EntityPhysics {
     /// Current position.
     pos: Vector2f,
 
+    // Position at the end of the previous tick, used for interpolation.
+    prev_pos: Vector2f,
+
     // Momentum - velocity * mass.
     momentum: Vector2f,
 
-    // // Velocity - change in pos per unit time. Must be kept consistent with momentum.
-    // vel: Vector2f,
-
     // Force - the force experienced by the object this frame.
     force: Vector2f,
 
-    // Linear Damping - the fraction of velocity lost per unit time.
+    // Linear Damping - the fraction of velocity lost per second.
     linear_damping: f32,
 
     // Mass - the mass of the object.
     mass: f32,
 
-    /// Current rotation.
+    /// Current rotation in degrees, always in ``[0, 360)``.
     rot: f32,
 
-    // Angular momentum.
+    // Rotation at the end of the previous tick, used for interpolation.
+    prev_rot: f32,
+
+    // Angular momentum, in terms of angular velocity in radians per second.
     angular_momentum: f32,
 
     // Torque - analog of force for rotation.
     torque: f32,
 
-    // Angular Damping - the fraction of rotational speed lost per unit time.
+    // Angular Damping - the fraction of rotational speed lost per second.
     angular_damping: f32,
 
     // Rotational inertia.
     rotational_inertia: f32,
+
+    // Integration scheme used by ``update``.
+    integrator: Integrator,
+
+    // Immovable objects ignore forces and impulses, but still have mass for gravity.
+    immovable: bool,
+
+    // Drag coefficient times cross-sectional area, used in atmospheres.
+    drag_area: f32,
+
+    // Heat built up by friction with an atmosphere.
+    heat: f32,
+
+    // Cooling - the fraction of heat lost per second.
+    cooling: f32,
+
+    // Bullets are swept between ticks so they can't pass through things.
+    bullet: bool,
 }
 
 impl EntityPhysics {
@@ -65,34 +97,64 @@ impl EntityPhysics {
     pub fn with_damping_pos<T: Into<Vector2f>>(mass: f32, rotational_inertia: f32,
                                                linear_damping: f32, angular_damping: f32, pos: T)
                                                -> EntityPhysics {
+        let pos = pos.into();
+
         EntityPhysics {
-            pos: pos.into(),
+            pos,
+            prev_pos: pos,
             momentum: Vector2f::new(0., 0.),
             force: Vector2f::new(0., 0.),
             linear_damping,
             mass,
             rot: 0.,
+            prev_rot: 0.,
             angular_momentum: 0.,
             torque: 0.,
             rotational_inertia,
             angular_damping,
+            integrator: Default::default(),
+            immovable: false,
+            drag_area: 1.,
+            heat: 0.,
+            cooling: DEFAULT_COOLING,
+            bullet: false,
         }
     }
 
-    /// Simulate one frame. This will set ``self.force`` to ``(0., 0.)``.
-    pub fn update(&mut self) {
+    /// Advance the simulation by ``dt`` seconds, with no external ``ForceField``.
+    /// This will set ``self.force`` to ``(0., 0.)``.
+    pub fn update(&mut self, dt: f32) {
+        self.update_in(dt, &NoField);
+    }
+
+    /// Advance the simulation by ``dt`` seconds using the selected ``Integrator``.
+    /// Forces applied since the last update are held constant over the step,
+    /// while ``field`` is sampled wherever the integrator needs it.
+    /// This will set ``self.force`` to ``(0., 0.)``.
+    pub fn update_in(&mut self, dt: f32, field: &dyn ForceField) {
+        self.prev_pos = self.pos;
+        self.prev_rot = self.rot;
+        self.heat *= integrator::damping_factor(self.cooling, dt);
+
+        if self.immovable {
+            self.force = Vector2f::new(0., 0.);
+            self.torque = 0.;
+            return;
+        }
+
         //
         // Linear
         //
-        self.momentum += self.force;
-        self.momentum *= 1.0 - self.linear_damping;
 
         // p = mv
         // vm = p
         // v = p / m
         let vel = self.momentum / self.mass;
 
-        self.pos += vel;
+        let (pos, vel) = self.integrator.integrate_linear(self.pos, vel, self.force,
+                                                          self.mass, dt, field);
+        self.pos = pos;
+        self.momentum = vel * self.mass * integrator::damping_factor(self.linear_damping, dt);
 
         // Reset the force for the next frame.
         self.force = Vector2f::new(0., 0.);
@@ -100,16 +162,19 @@ impl EntityPhysics {
         //
         // Angular
         //
-        self.angular_momentum += self.torque;
-        self.angular_momentum *= 1.0 - self.angular_damping;
 
         // L = Iω
         // ωI = L
         // ω = L / I
         let ang_vel = self.angular_momentum / self.rotational_inertia;
+        let ang_accel = self.torque / self.rotational_inertia;
 
-        self.rot += ang_vel;
-        self.clamp_rot();
+        // Rotation is kept in degrees for SFML, angular velocity in radians.
+        let (turned, ang_vel) = self.integrator.integrate_angular(0., ang_vel, ang_accel, dt);
+        self.rot += turned.to_degrees();
+        self.normalize_rot();
+        self.angular_momentum = ang_vel * self.rotational_inertia
+            * integrator::damping_factor(self.angular_damping, dt);
 
         // Reset the torque for the next frame.
         self.torque = 0.;
@@ -139,33 +204,178 @@ impl EntityPhysics {
         self.apply_torque(-((force.x * dist.y) - (force.y * dist.x)));
     }
 
+    /// Apply a force at a point, both given in the object's own (rotated)
+    /// coordinate frame, relative to its center. This is how engines mounted
+    /// on a ship push it: the force turns with the ship.
+    pub fn apply_local_force_at<T1, T2>(&mut self, local_force: T1, local_point: T2)
+        where T1: Into<Vector2f>,
+              T2: Into<Vector2f>
+    {
+        let force = self.local_to_world_vector(local_force);
+        let point = self.local_to_world(local_point);
+        self.apply_force_at(force, point);
+    }
+
     /// Apply a torque.
     /// This will induce only rotational motion.
     pub fn apply_torque(&mut self, t: f32) {
         self.torque += t;
     }
 
+    /// Apply an instantaneous impulse at the object's center, changing
+    /// momentum immediately rather than over the next update.
+    pub fn apply_impulse<T: Into<Vector2f>>(&mut self, impulse: T) {
+        if !self.immovable {
+            self.momentum += impulse.into();
+        }
+    }
+
+    /// Apply an instantaneous impulse at a point in world coords.
+    /// Like ``apply_force_at``, this changes both linear and angular momentum.
+    pub fn apply_impulse_at<T1, T2>(&mut self, impulse: T1, point: T2)
+        where T1: Into<Vector2f>,
+              T2: Into<Vector2f>
+    {
+        let impulse = impulse.into();
+        let dist = point.into() - self.pos;
+
+        self.apply_impulse(impulse);
+        self.apply_angular_impulse(dist.cross(&impulse));
+    }
+
+    /// Apply an instantaneous impulse at a point given relative to the object's center,
+    /// in the object's own (rotated) coordinate frame. The impulse itself is in world space.
+    pub fn apply_impulse_at_local<T1, T2>(&mut self, impulse: T1, local_point: T2)
+        where T1: Into<Vector2f>,
+              T2: Into<Vector2f>
+    {
+        let point = self.local_to_world(local_point);
+        self.apply_impulse_at(impulse, point);
+    }
+
+    /// Apply an instantaneous angular impulse.
+    pub fn apply_angular_impulse(&mut self, impulse: f32) {
+        if !self.immovable {
+            self.angular_momentum += impulse;
+        }
+    }
+
     /// Set the position of the object directly.
+    /// This is a teleport, so no interpolation happens from the old position.
     pub fn set_position<T: Into<Vector2f>>(&mut self, pos: T) {
         self.pos = pos.into();
+        self.prev_pos = self.pos;
     }
 
-    /// Set the rotation of the object directly.
+    /// Move the object by an offset. Unlike ``set_position``, this is
+    /// interpolated like regular motion, so it suits small corrections.
+    pub fn translate<T: Into<Vector2f>>(&mut self, offset: T) {
+        self.pos += offset.into();
+    }
+
+    /// Turn the object by ``degrees``. Unlike ``set_rotation``, this is
+    /// interpolated like regular motion, so it suits small corrections.
+    pub fn turn(&mut self, degrees: f32) {
+        self.rot += degrees;
+        self.normalize_rot();
+    }
+
+    /// Set the rotation of the object directly, in degrees.
+    /// Like ``set_position``, this is not interpolated.
     pub fn set_rotation(&mut self, rot: f32) {
         self.rot = rot;
-        self.clamp_rot();
+        self.normalize_rot();
+        self.prev_rot = self.rot;
+    }
+
+    /// Set the rotation of the object directly, in radians.
+    /// Like ``set_position``, this is not interpolated.
+    pub fn set_rotation_radians(&mut self, rot: f32) {
+        self.set_rotation(rot.to_degrees());
     }
 
-    /// Set linear damping - the fraction of velocity lost per unit time.
+    /// Set linear damping - the fraction of velocity lost per second.
     pub fn set_linear_damping(&mut self, linear_damping: f32) {
         self.linear_damping = linear_damping;
     }
 
-    /// Set angular damping - the fraction of rotational speed lost per unit time.
+    /// Set angular damping - the fraction of rotational speed lost per second.
     pub fn set_angular_damping(&mut self, angular_damping: f32) {
         self.angular_damping = angular_damping;
     }
 
+    /// Make the object immovable, or movable again.
+    /// Immovable objects ignore forces and impulses and never move on their own,
+    /// but they still have mass, so planets can be immovable and still attract.
+    /// Making an object immovable stops it, though its velocity can still be set
+    /// directly for objects that are moved by hand.
+    pub fn set_immovable(&mut self, immovable: bool) {
+        self.immovable = immovable;
+        if immovable {
+            self.momentum = Vector2f::new(0., 0.);
+            self.angular_momentum = 0.;
+        }
+    }
+
+    /// Flag the object as a bullet. Collisions with bullets are found by
+    /// sweeping them from their previous to their current position, so fast,
+    /// small objects hit things they would otherwise skip over between ticks.
+    /// This costs more, so only flag things that need it.
+    pub fn set_bullet(&mut self, bullet: bool) {
+        self.bullet = bullet;
+    }
+
+    /// Select the integration scheme used by ``update``.
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.integrator = integrator;
+    }
+
+    /// Set the velocity directly, in units per second.
+    pub fn set_vel<T: Into<Vector2f>>(&mut self, vel: T) {
+        self.momentum = vel.into() * self.mass;
+    }
+
+    /// Set the momentum directly.
+    pub fn set_momentum<T: Into<Vector2f>>(&mut self, momentum: T) {
+        self.momentum = momentum.into();
+    }
+
+    /// Set the angular velocity directly, in degrees per second.
+    pub fn set_angular_vel(&mut self, angular_vel: f32) {
+        self.angular_momentum = angular_vel.to_radians() * self.rotational_inertia;
+    }
+
+    /// Set the angular velocity directly, in radians per second.
+    pub fn set_angular_vel_radians(&mut self, angular_vel: f32) {
+        self.angular_momentum = angular_vel * self.rotational_inertia;
+    }
+
+    /// Set the angular momentum directly.
+    pub fn set_angular_momentum(&mut self, angular_momentum: f32) {
+        self.angular_momentum = angular_momentum;
+    }
+
+    /// Set the drag area - drag coefficient times cross-sectional area.
+    /// Bigger values slow down faster in an atmosphere. Defaults to ``1``.
+    pub fn set_drag_area(&mut self, drag_area: f32) {
+        self.drag_area = drag_area;
+    }
+
+    /// Set the heat directly.
+    pub fn set_heat(&mut self, heat: f32) {
+        self.heat = heat;
+    }
+
+    /// Add heat, such as from friction with an atmosphere.
+    pub fn add_heat(&mut self, heat: f32) {
+        self.heat += heat;
+    }
+
+    /// Set cooling - the fraction of heat lost per second.
+    pub fn set_cooling(&mut self, cooling: f32) {
+        self.cooling = cooling;
+    }
+
     /// Convenience method to set the damping values for both linear and rotational motion.
     pub fn set_damping(&mut self, linear_damping: f32, angular_damping: f32) {
         self.set_linear_damping(linear_damping);
@@ -177,11 +387,86 @@ impl EntityPhysics {
         self.pos
     }
 
-    /// Current rotation.
+    /// Current rotation in degrees, in ``[0, 360)``.
+    /// Positive rotation is clockwise on screen, since y points down.
     pub fn rot(&self) -> f32 {
         self.rot
     }
 
+    /// Current rotation in radians, in ``[0, 2π)``.
+    pub fn rot_radians(&self) -> f32 {
+        self.rot.to_radians()
+    }
+
+    /// The direction the object faces, as a unit vector in world space.
+    /// Objects face up, along negative y, when their rotation is zero, so draw
+    /// sprites facing up.
+    pub fn forward(&self) -> Vector2f {
+        self.local_to_world_vector((0., -1.))
+    }
+
+    /// The direction to the object's right, as a unit vector in world space.
+    /// This is ``forward`` turned a quarter turn clockwise.
+    pub fn right(&self) -> Vector2f {
+        self.local_to_world_vector((1., 0.))
+    }
+
+    /// Convert a point in the object's own (rotated) coordinate frame,
+    /// relative to its center, to world coordinates.
+    pub fn local_to_world<T: Into<Vector2f>>(&self, local_point: T) -> Vector2f {
+        self.pos + self.local_to_world_vector(local_point)
+    }
+
+    /// Convert a point in world coordinates to the object's own frame.
+    /// The inverse of ``local_to_world``.
+    pub fn world_to_local<T: Into<Vector2f>>(&self, point: T) -> Vector2f {
+        self.world_to_local_vector(point.into() - self.pos)
+    }
+
+    /// Convert a direction or offset in the object's own frame to world space.
+    /// Unlike ``local_to_world``, this only rotates, so it suits forces and velocities.
+    pub fn local_to_world_vector<T: Into<Vector2f>>(&self, local_vector: T) -> Vector2f {
+        local_vector.into().rotate(self.rot)
+    }
+
+    /// Convert a direction or offset in world space to the object's own frame.
+    /// The inverse of ``local_to_world_vector``.
+    pub fn world_to_local_vector<T: Into<Vector2f>>(&self, vector: T) -> Vector2f {
+        vector.into().rotate(-self.rot)
+    }
+
+    /// Position at the end of the previous tick.
+    pub fn prev_pos(&self) -> Vector2f {
+        self.prev_pos
+    }
+
+    /// Rotation at the end of the previous tick.
+    pub fn prev_rot(&self) -> f32 {
+        self.prev_rot
+    }
+
+    /// Position blended between the previous and current tick.
+    /// ``alpha`` is the fraction of a tick elapsed, usually ``GameClock::alpha()``.
+    pub fn interpolated_pos(&self, alpha: f32) -> Vector2f {
+        self.prev_pos + (self.pos - self.prev_pos) * alpha
+    }
+
+    /// Rotation blended between the previous and current tick, in ``[0, 360)``.
+    /// Always turns the short way, even across the wrap point.
+    pub fn interpolated_rot(&self, alpha: f32) -> f32 {
+        util::normalize_angle(self.prev_rot + util::angle_delta(self.prev_rot, self.rot) * alpha)
+    }
+
+    /// The force applied since the last update, which the next update will use.
+    pub fn force(&self) -> Vector2f {
+        self.force
+    }
+
+    /// The torque applied since the last update, which the next update will use.
+    pub fn torque(&self) -> f32 {
+        self.torque
+    }
+
     /// Return linear damping.
     pub fn linear_damping(&self) -> f32 {
         self.linear_damping
@@ -192,6 +477,26 @@ impl EntityPhysics {
         self.angular_damping
     }
 
+    /// Return the drag area.
+    pub fn drag_area(&self) -> f32 {
+        self.drag_area
+    }
+
+    /// Return the heat built up, in the same units as energy.
+    pub fn heat(&self) -> f32 {
+        self.heat
+    }
+
+    /// Return cooling.
+    pub fn cooling(&self) -> f32 {
+        self.cooling
+    }
+
+    /// Return the integration scheme.
+    pub fn integrator(&self) -> Integrator {
+        self.integrator
+    }
+
     /// Return the mass of the object.
     pub fn mass(&self) -> f32 {
         self.mass
@@ -202,19 +507,92 @@ impl EntityPhysics {
         self.rotational_inertia
     }
 
-    /*
-    /// Velocity - change in `pos` per unit time.
+    /// Return whether the object is a bullet.
+    pub fn is_bullet(&self) -> bool {
+        self.bullet
+    }
+
+    /// Return whether the object is immovable.
+    pub fn is_immovable(&self) -> bool {
+        self.immovable
+    }
+
+    /// The reciprocal of mass, or zero for immovable objects.
+    /// This is what collision response and constraints work with.
+    pub fn inv_mass(&self) -> f32 {
+        if self.immovable { 0. } else { 1. / self.mass }
+    }
+
+    /// The reciprocal of rotational inertia, or zero for immovable objects.
+    pub fn inv_rotational_inertia(&self) -> f32 {
+        if self.immovable { 0. } else { 1. / self.rotational_inertia }
+    }
+
+    /// Velocity - change in ``pos`` per second.
     pub fn vel(&self) -> Vector2f {
-        self.vel
+        self.momentum / self.mass
     }
-    */
 
-    fn clamp_rot(&mut self) {
-        if self.rot > 360. {
-            self.rot -= 360.;
-        } else if self.rot < -360. {
-            self.rot += 360.;
-        }
+    /// Speed - the length of ``vel``.
+    pub fn speed(&self) -> f32 {
+        self.vel().length()
+    }
+
+    /// Momentum - velocity * mass.
+    pub fn momentum(&self) -> Vector2f {
+        self.momentum
+    }
+
+    /// Angular velocity - change in ``rot`` per second, in degrees.
+    pub fn angular_vel(&self) -> f32 {
+        self.angular_vel_radians().to_degrees()
+    }
+
+    /// Angular velocity in radians per second.
+    pub fn angular_vel_radians(&self) -> f32 {
+        self.angular_momentum / self.rotational_inertia
+    }
+
+    /// Angular momentum - rotational inertia * angular velocity in radians per second.
+    pub fn angular_momentum(&self) -> f32 {
+        self.angular_momentum
+    }
+
+    /// The velocity of a point in world coords, as if it were attached to the object.
+    /// Includes the contribution of rotation.
+    pub fn vel_at<T: Into<Vector2f>>(&self, point: T) -> Vector2f {
+        let dist = point.into() - self.pos;
+        self.vel() + dist.perp() * self.angular_vel_radians()
+    }
+
+    /// Kinetic energy of linear motion.
+    pub fn linear_kinetic_energy(&self) -> f32 {
+        0.5 * self.momentum.length_sq() / self.mass
+    }
+
+    /// Kinetic energy of rotation.
+    pub fn angular_kinetic_energy(&self) -> f32 {
+        0.5 * self.angular_momentum * self.angular_momentum / self.rotational_inertia
+    }
+
+    /// Total kinetic energy, linear and rotational.
+    pub fn kinetic_energy(&self) -> f32 {
+        self.linear_kinetic_energy() + self.angular_kinetic_energy()
+    }
+
+    /// Potential energy in a ``ForceField``, such as a ``PlanetManager``.
+    pub fn potential_energy(&self, field: &dyn ForceField) -> f32 {
+        field.potential_at(self.pos, self.mass)
+    }
+
+    /// Kinetic plus potential energy in a ``ForceField``.
+    /// Stays roughly constant for an undamped body in orbit.
+    pub fn total_energy(&self, field: &dyn ForceField) -> f32 {
+        self.kinetic_energy() + self.potential_energy(field)
+    }
+
+    fn normalize_rot(&mut self) {
+        self.rot = util::normalize_angle(self.rot);
     }
 }
 
@@ -233,12 +611,46 @@ impl Default for EntityPhysics {
 
 /// Functionality of an entity.
 pub trait Entity {
-    /// Perform per-frame logic.
-    fn update(&mut self);
+    /// Perform per-tick logic, advancing by ``dt`` seconds
+    /// under the influence of ``field``.
+    fn update(&mut self, dt: f32, field: &dyn ForceField);
 
     /// Access the physics component.
     fn phys(&self) -> &EntityPhysics;
 
     /// Mutably access the physics component.
     fn phys_mut(&mut self) -> &mut EntityPhysics;
+
+    /// Access the collision shape, if the entity has one.
+    fn collider(&self) -> Option<&Collider> {
+        None
+    }
+
+    /// Move whatever is drawn to the physics transform blended between the
+    /// previous and current tick. Call this once per frame, before drawing.
+    /// Does nothing unless the entity draws something.
+    fn interpolate(&mut self, _alpha: f32) {}
+}
+
+impl SimState for EntityPhysics {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_vector(self.pos());
+        hasher.write_vector(self.prev_pos());
+        hasher.write_vector(self.momentum());
+        hasher.write_vector(self.force());
+        hasher.write_f32(self.linear_damping());
+        hasher.write_f32(self.mass());
+        hasher.write_f32(self.rot());
+        hasher.write_f32(self.prev_rot());
+        hasher.write_f32(self.angular_momentum());
+        hasher.write_f32(self.torque());
+        hasher.write_f32(self.angular_damping());
+        hasher.write_f32(self.rotational_inertia());
+        hasher.write_u8(self.integrator() as u8);
+        hasher.write_bool(self.is_immovable());
+        hasher.write_bool(self.is_bullet());
+        hasher.write_f32(self.drag_area());
+        hasher.write_f32(self.heat());
+        hasher.write_f32(self.cooling());
+    }
 }
@@ -1,7 +1,9 @@
 //! An entity that uses `RcSprite`.
 
 use sfml::graphics::{Drawable, RenderStates, RenderTarget, Transformable};
-use super::{Entity, EntityPhysics};
+use super::{Entity, EntityPhysics, ForceField, Thrusters};
+use checksum::{SimState, StateHasher};
+use collision::Collider;
 use refcounted::{RcSprite, RcTexture};
 
 /// A visable entity that is composed of an `RcSprite`
@@ -9,6 +11,8 @@ use refcounted::{RcSprite, RcTexture};
 pub struct SpriteEntity {
     sprite: RcSprite,
     phys: EntityPhysics,
+    collider: Option<Collider>,
+    thrusters: Option<Thrusters>,
 }
 
 impl SpriteEntity {
@@ -36,9 +40,43 @@ impl SpriteEntity {
         SpriteEntity {
             sprite,
             phys,
+            collider: None,
+            thrusters: None,
         }
     }
 
+    /// Attach a collision shape, or remove it with ``None``.
+    pub fn set_collider(&mut self, collider: Option<Collider>) {
+        self.collider = collider;
+    }
+
+    /// Mutably access the collision shape, if there is one.
+    pub fn collider_mut(&mut self) -> Option<&mut Collider> {
+        self.collider.as_mut()
+    }
+
+    /// Mount a set of thrusters, or remove them with ``None``.
+    /// Mounted thrusters fire on every ``update``.
+    pub fn set_thrusters(&mut self, thrusters: Option<Thrusters>) {
+        self.thrusters = thrusters;
+    }
+
+    /// Access the thrusters, if any are mounted.
+    pub fn thrusters(&self) -> Option<&Thrusters> {
+        self.thrusters.as_ref()
+    }
+
+    /// Mutably access the thrusters, if any are mounted, to set throttles.
+    pub fn thrusters_mut(&mut self) -> Option<&mut Thrusters> {
+        self.thrusters.as_mut()
+    }
+
+    /// Break the entity into its sprite, physics, collider and thrusters,
+    /// such as to spawn it into an ``ecs::Registry``.
+    pub fn into_parts(self) -> (RcSprite, EntityPhysics, Option<Collider>, Option<Thrusters>) {
+        (self.sprite, self.phys, self.collider, self.thrusters)
+    }
+
     /// Access the inner `RcSprite`.
     pub fn rc_sprite(&self) -> &RcSprite {
         &self.sprite
@@ -56,8 +94,12 @@ impl SpriteEntity {
 }
 
 impl Entity for SpriteEntity {
-    fn update(&mut self) {
-        self.phys.update();
+    fn update(&mut self, dt: f32, field: &dyn ForceField) {
+        if let Some(ref mut thrusters) = self.thrusters {
+            thrusters.fire(&mut self.phys, dt);
+        }
+
+        self.phys.update_in(dt, field);
         Self::update_sprite(&self.phys, &mut self.sprite);
     }
 
@@ -68,6 +110,15 @@ impl Entity for SpriteEntity {
     fn phys_mut(&mut self) -> &mut EntityPhysics {
         &mut self.phys
     }
+
+    fn collider(&self) -> Option<&Collider> {
+        self.collider.as_ref()
+    }
+
+    fn interpolate(&mut self, alpha: f32) {
+        self.sprite.set_position(self.phys.interpolated_pos(alpha));
+        self.sprite.set_rotation(self.phys.interpolated_rot(alpha));
+    }
 }
 
 impl Drawable for SpriteEntity {
@@ -78,3 +129,13 @@ impl Drawable for SpriteEntity {
         self.sprite.draw(target, states);
     }
 }
+
+impl SimState for SpriteEntity {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        self.phys().hash_state(hasher);
+        hasher.write_bool(self.thrusters().is_some());
+        if let Some(thrusters) = self.thrusters() {
+            thrusters.hash_state(hasher);
+        }
+    }
+}
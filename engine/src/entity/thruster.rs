@@ -0,0 +1,200 @@
+//! Thrusters: engines mounted on a body that push it in the direction it faces.
+
+use std::hash::Hasher;
+use sfml::system::Vector2f;
+use checksum::{SimState, StateHasher};
+use util::Vector2Ext;
+use super::EntityPhysics;
+
+/// A single engine, mounted at a fixed place on a body.
+/// Positions and directions are in the body's own (rotated) coordinate frame,
+/// so turning the body turns its thrusters with it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Thruster {
+    // Mount point relative to the body's center.
+    offset: Vector2f,
+
+    // Unit vector in the direction the thruster pushes the body.
+    direction: Vector2f,
+
+    // Force at full throttle.
+    max_thrust: f32,
+
+    // Fraction of ``max_thrust`` in use, from 0 to 1.
+    throttle: f32,
+
+    // Fuel burned per second at full throttle.
+    fuel_rate: f32,
+}
+
+impl Thruster {
+    /// Create a thruster at ``offset`` from the body's center that pushes the
+    /// body along ``direction``. It starts switched off and burns no fuel.
+    /// Panics if ``direction`` is zero.
+    pub fn new<T1, T2>(offset: T1, direction: T2, max_thrust: f32) -> Thruster
+        where T1: Into<Vector2f>,
+              T2: Into<Vector2f>
+    {
+        let direction = direction.into();
+        assert!(direction != Vector2f::new(0., 0.), "a thruster needs a direction");
+
+        Thruster {
+            offset: offset.into(),
+            direction: direction.normalize(),
+            max_thrust,
+            throttle: 0.,
+            fuel_rate: 0.,
+        }
+    }
+
+    /// Set the throttle, from ``0`` (off) to ``1`` (full thrust).
+    /// Values outside that range are clamped.
+    pub fn set_throttle(&mut self, throttle: f32) {
+        self.throttle = throttle.clamp(0., 1.);
+    }
+
+    /// Set the maximum thrust.
+    pub fn set_max_thrust(&mut self, max_thrust: f32) {
+        self.max_thrust = max_thrust;
+    }
+
+    /// Set the fuel burned per second at full throttle.
+    pub fn set_fuel_rate(&mut self, fuel_rate: f32) {
+        self.fuel_rate = fuel_rate;
+    }
+
+    /// Return the mount point relative to the body's center.
+    pub fn offset(&self) -> Vector2f {
+        self.offset
+    }
+
+    /// Return the direction the thruster pushes the body, in body coordinates.
+    pub fn direction(&self) -> Vector2f {
+        self.direction
+    }
+
+    /// Return the maximum thrust.
+    pub fn max_thrust(&self) -> f32 {
+        self.max_thrust
+    }
+
+    /// Return the throttle.
+    pub fn throttle(&self) -> f32 {
+        self.throttle
+    }
+
+    /// Return the fuel burned per second at full throttle.
+    pub fn fuel_rate(&self) -> f32 {
+        self.fuel_rate
+    }
+
+    /// The force the thruster produces at its current throttle, in body coordinates.
+    pub fn force(&self) -> Vector2f {
+        self.direction * (self.max_thrust * self.throttle)
+    }
+
+    /// Fuel burned per second at the current throttle.
+    pub fn fuel_use(&self) -> f32 {
+        self.fuel_rate * self.throttle
+    }
+}
+
+/// A set of thrusters sharing one fuel tank.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Thrusters {
+    thrusters: Vec<Thruster>,
+    fuel: f32,
+}
+
+impl Thrusters {
+    /// Create an empty set of thrusters with ``fuel`` in the tank.
+    /// Use ``f32::INFINITY`` for a tank that never runs out.
+    pub fn new(fuel: f32) -> Thrusters {
+        Thrusters {
+            thrusters: Vec::new(),
+            fuel,
+        }
+    }
+
+    /// Mount a thruster, returning its index.
+    pub fn add(&mut self, thruster: Thruster) -> usize {
+        self.thrusters.push(thruster);
+        self.thrusters.len() - 1
+    }
+
+    /// Access a thruster by index.
+    pub fn get(&self, index: usize) -> Option<&Thruster> {
+        self.thrusters.get(index)
+    }
+
+    /// Mutably access a thruster by index.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Thruster> {
+        self.thrusters.get_mut(index)
+    }
+
+    /// Access all thrusters, in the order they were added.
+    pub fn thrusters(&self) -> &[Thruster] {
+        &self.thrusters
+    }
+
+    /// Set the throttle of a thruster by index. See ``Thruster::set_throttle``.
+    pub fn set_throttle(&mut self, index: usize, throttle: f32) {
+        self.thrusters[index].set_throttle(throttle);
+    }
+
+    /// Switch every thruster off.
+    pub fn cut_all(&mut self) {
+        for thruster in &mut self.thrusters {
+            thruster.set_throttle(0.);
+        }
+    }
+
+    /// Set the fuel in the tank.
+    pub fn set_fuel(&mut self, fuel: f32) {
+        self.fuel = fuel;
+    }
+
+    /// Return the fuel left in the tank.
+    pub fn fuel(&self) -> f32 {
+        self.fuel
+    }
+
+    /// Whether the tank has run dry.
+    pub fn out_of_fuel(&self) -> bool {
+        self.fuel <= 0.
+    }
+
+    /// Fire every thruster for ``dt`` seconds at its throttle, applying the
+    /// forces to ``phys`` and burning fuel. When the tank can't cover a full
+    /// step, every thruster is weakened evenly to use up what is left.
+    pub fn fire(&mut self, phys: &mut EntityPhysics, dt: f32) {
+        let demand: f32 = self.thrusters.iter().map(Thruster::fuel_use).sum::<f32>() * dt;
+        let scale = if demand > self.fuel {
+            self.fuel.max(0.) / demand
+        } else {
+            1.
+        };
+
+        if scale <= 0. {
+            return;
+        }
+
+        for thruster in &self.thrusters {
+            if thruster.throttle > 0. {
+                phys.apply_local_force_at(thruster.force() * scale, thruster.offset);
+            }
+        }
+
+        self.fuel -= demand * scale;
+    }
+}
+
+impl SimState for Thrusters {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_f32(self.fuel());
+        hasher.write_usize(self.thrusters().len());
+        for thruster in self.thrusters() {
+            hasher.write_f32(thruster.throttle());
+        }
+    }
+}
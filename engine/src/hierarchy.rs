@@ -0,0 +1,247 @@
+//! Entities that ride on other entities, such as turrets, engine flames and
+//! docking arms on a ship.
+//!
+//! A ``Hierarchy`` remembers each child's pose relative to its parent's
+//! ``EntityPhysics``: an offset in the parent's local frame and a rotation on
+//! top of the parent's. Once per tick, after physics and collisions, call
+//! ``propagate`` to put every child back where its parent says it should be,
+//! moving with the parent's velocity. Parents are placed before their children,
+//! so chains of any depth settle in one pass.
+//!
+//! Entities are named by ``Handle``s, and their physics is looked up through
+//! ``Bodies``, which both ``World`` and the ``EntityPhysics`` storage of an
+//! ``ecs::Registry`` implement. Moons follow planets with ``Rails`` instead.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hasher;
+use sfml::system::Vector2f;
+use checksum::{SimState, StateHasher};
+use ecs::Storage;
+use entity::EntityPhysics;
+use util;
+use world::{Handle, World};
+
+/// Somewhere to find the physics of entities by handle.
+pub trait Bodies {
+    /// Access an entity's physics.
+    fn body(&self, entity: Handle) -> Option<&EntityPhysics>;
+
+    /// Mutably access an entity's physics.
+    fn body_mut(&mut self, entity: Handle) -> Option<&mut EntityPhysics>;
+}
+
+impl Bodies for World {
+    fn body(&self, entity: Handle) -> Option<&EntityPhysics> {
+        self.get(entity).map(|entity| entity.phys())
+    }
+
+    fn body_mut(&mut self, entity: Handle) -> Option<&mut EntityPhysics> {
+        self.get_mut(entity).map(|entity| entity.phys_mut())
+    }
+}
+
+impl Bodies for Storage<EntityPhysics> {
+    fn body(&self, entity: Handle) -> Option<&EntityPhysics> {
+        self.get(entity)
+    }
+
+    fn body_mut(&mut self, entity: Handle) -> Option<&mut EntityPhysics> {
+        self.get_mut(entity)
+    }
+}
+
+/// A child's pose relative to its parent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LocalTransform {
+    /// Position in the parent's local frame.
+    pub pos: Vector2f,
+
+    /// Rotation in degrees, on top of the parent's.
+    pub rot: f32,
+}
+
+impl LocalTransform {
+    /// Create a local transform.
+    pub fn new<V: Into<Vector2f>>(pos: V, rot: f32) -> LocalTransform {
+        LocalTransform {
+            pos: pos.into(),
+            rot,
+        }
+    }
+
+    /// The pose of ``child`` relative to ``parent`` as they are now.
+    pub fn between(parent: &EntityPhysics, child: &EntityPhysics) -> LocalTransform {
+        LocalTransform {
+            pos: parent.world_to_local(child.pos()),
+            rot: util::angle_delta(parent.rot(), child.rot()),
+        }
+    }
+}
+
+/// Parent-child links between entities. See the module documentation.
+#[derive(Clone, Debug, Default)]
+pub struct Hierarchy {
+    // Ordered so ``links`` come out the same way every run.
+    links: BTreeMap<Handle, (Handle, LocalTransform)>,
+    children: HashMap<Handle, Vec<Handle>>,
+}
+
+impl Hierarchy {
+    /// Create a hierarchy with no links.
+    pub fn new() -> Hierarchy {
+        Default::default()
+    }
+
+    /// Attach ``child`` to ``parent`` where it is now, so it keeps its world
+    /// pose. A child that already has a parent moves over to the new one.
+    /// Returns ``false`` and changes nothing if either entity can't be found,
+    /// or if the link would make a loop.
+    pub fn attach<B: Bodies + ?Sized>(&mut self, bodies: &B, child: Handle, parent: Handle)
+                                      -> bool {
+        let local = match (bodies.body(parent), bodies.body(child)) {
+            (Some(parent), Some(child)) => LocalTransform::between(parent, child),
+            _ => return false,
+        };
+
+        self.attach_at(child, parent, local)
+    }
+
+    /// Attach ``child`` to ``parent`` at a given local pose, such as a mount
+    /// point. The child jumps there on the next ``propagate``.
+    /// Returns ``false`` and changes nothing if the link would make a loop.
+    pub fn attach_at(&mut self, child: Handle, parent: Handle, local: LocalTransform) -> bool {
+        if self.is_ancestor(child, parent) {
+            return false;
+        }
+
+        self.detach(child);
+        self.links.insert(child, (parent, local));
+        self.children.entry(parent).or_default().push(child);
+        true
+    }
+
+    /// Detach a child from its parent, returning the parent. The child stays
+    /// where it was last propagated to, and keeps its own children.
+    pub fn detach(&mut self, child: Handle) -> Option<Handle> {
+        let (parent, _) = self.links.remove(&child)?;
+
+        let now_empty = match self.children.get_mut(&parent) {
+            Some(siblings) => {
+                siblings.retain(|&sibling| sibling != child);
+                siblings.is_empty()
+            },
+            None => false,
+        };
+
+        if now_empty {
+            self.children.remove(&parent);
+        }
+
+        Some(parent)
+    }
+
+    /// Forget an entity that is being despawned. Its children are detached
+    /// where they are and become free.
+    pub fn remove(&mut self, entity: Handle) {
+        self.detach(entity);
+        for child in self.children.remove(&entity).unwrap_or_default() {
+            self.links.remove(&child);
+        }
+    }
+
+    /// Return an entity's parent.
+    pub fn parent(&self, child: Handle) -> Option<Handle> {
+        self.links.get(&child).map(|&(parent, _)| parent)
+    }
+
+    /// Return an entity's children, in the order they were attached.
+    pub fn children(&self, parent: Handle) -> &[Handle] {
+        self.children.get(&parent).map_or(&[], Vec::as_slice)
+    }
+
+    /// Return a child's pose relative to its parent.
+    pub fn local_transform(&self, child: Handle) -> Option<LocalTransform> {
+        self.links.get(&child).map(|&(_, local)| local)
+    }
+
+    /// Move a child relative to its parent, such as to aim a turret.
+    /// Returns ``false`` if it has no parent.
+    pub fn set_local_transform(&mut self, child: Handle, local: LocalTransform) -> bool {
+        match self.links.get_mut(&child) {
+            Some(&mut (_, ref mut old)) => {
+                *old = local;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Iterate over every link as ``(child, parent, local transform)``, in order of the child.
+    pub fn links(&self) -> impl Iterator<Item = (Handle, Handle, LocalTransform)> + '_ {
+        self.links.iter().map(|(&child, &(parent, local))| (child, parent, local))
+    }
+
+    /// Test whether ``ancestor`` is ``entity`` or anything above it.
+    pub fn is_ancestor(&self, ancestor: Handle, entity: Handle) -> bool {
+        let mut current = Some(entity);
+        while let Some(entity) = current {
+            if entity == ancestor {
+                return true;
+            }
+            current = self.parent(entity);
+        }
+
+        false
+    }
+
+    /// Place every child according to its parent, parents first.
+    /// Children move like regular motion rather than teleporting, so they
+    /// interpolate smoothly, and take on the velocity of the point of the
+    /// parent they are attached to. Links to missing entities are skipped.
+    pub fn propagate<B: Bodies + ?Sized>(&self, bodies: &mut B) {
+        let roots = self.children.keys().filter(|&&parent| !self.links.contains_key(&parent));
+        let mut roots: Vec<Handle> = roots.cloned().collect();
+        roots.sort_unstable();
+
+        let mut stack: Vec<Handle> = roots.into_iter().rev().collect();
+        while let Some(parent) = stack.pop() {
+            let parent_phys = match bodies.body(parent) {
+                Some(phys) => phys.clone(),
+                None => continue,
+            };
+
+            for &child in self.children(parent) {
+                let local = self.links[&child].1;
+                if let Some(phys) = bodies.body_mut(child) {
+                    place(phys, &parent_phys, &local);
+                }
+            }
+
+            stack.extend(self.children(parent).iter().rev());
+        }
+    }
+}
+
+// Move ``child`` to ``local`` in ``parent``'s frame, moving with it.
+fn place(child: &mut EntityPhysics, parent: &EntityPhysics, local: &LocalTransform) {
+    let pos = parent.local_to_world(local.pos);
+    let offset = pos - child.pos();
+    let turn = util::angle_delta(child.rot(), parent.rot() + local.rot);
+
+    child.translate(offset);
+    child.turn(turn);
+    child.set_vel(parent.vel_at(pos));
+    child.set_angular_vel(parent.angular_vel());
+}
+
+impl SimState for Hierarchy {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.links.len());
+        for (child, parent, local) in self.links() {
+            child.hash_state(hasher);
+            parent.hash_state(hasher);
+            hasher.write_vector(local.pos);
+            hasher.write_f32(local.rot);
+        }
+    }
+}
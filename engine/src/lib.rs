@@ -6,11 +6,20 @@ extern crate num_traits;
 extern crate sfml;
 
 pub mod background;
+pub mod behaviour;
+pub mod checksum;
+pub mod collision;
+pub mod constraint;
+pub mod ecs;
 pub mod entity;
+pub mod hierarchy;
 pub mod input;
 pub mod planet_manager;
 pub mod refcounted;
 pub mod resources;
 pub mod starfield;
+pub mod steering;
+pub mod time;
 pub mod util;
 pub mod window;
+pub mod world;
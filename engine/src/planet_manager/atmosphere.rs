@@ -0,0 +1,173 @@
+//! Atmospheres: layers of air around planets that slow and heat things
+//! passing through them.
+//!
+//! Drag follows the usual quadratic law, ``F = ½ ρ v² C A``, against the
+//! velocity relative to the planet, so the air moves along with it. ``C A`` is
+//! the drag area of the ``EntityPhysics``. Some of the energy drag takes away
+//! turns into heat on the entity, which makes re-entry hot and aerobraking a
+//! trade-off.
+
+use sfml::system::Vector2f;
+use entity::{Entity, EntityPhysics};
+use util::Vector2Ext;
+use super::PlanetManager;
+
+/// How the density of an atmosphere falls off with altitude.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DensityCurve {
+    /// The same density all the way up.
+    Constant,
+
+    /// Falls off in a straight line, reaching zero at the top.
+    Linear,
+
+    /// Falls off like a real atmosphere, thinning by a factor of ``e`` every
+    /// ``scale_height``. Shifted down slightly so it reaches zero at the top.
+    Exponential {
+        /// Altitude over which the density falls by a factor of ``e``. Must be positive.
+        scale_height: f32,
+    },
+
+    /// Falls off with the square of the remaining height, giving a thick
+    /// lower layer and a long, thin upper one. This is the default.
+    #[default]
+    Quadratic,
+}
+
+/// A layer of air around a planet. See the module documentation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Atmosphere {
+    /// How far the atmosphere reaches above the planet's surface.
+    pub height: f32,
+
+    /// The density of the air at the surface.
+    pub surface_density: f32,
+
+    /// How the density falls off between the surface and ``height``.
+    pub curve: DensityCurve,
+
+    /// The fraction of the energy taken away by drag that turns into heat.
+    pub heating: f32,
+}
+
+impl Atmosphere {
+    /// Create an atmosphere with the default ``DensityCurve`` that turns
+    /// half of the energy lost to drag into heat.
+    pub fn new(height: f32, surface_density: f32) -> Atmosphere {
+        Atmosphere {
+            height,
+            surface_density,
+            curve: Default::default(),
+            heating: 0.5,
+        }
+    }
+
+    /// Create an atmosphere with a given ``DensityCurve``.
+    /// Panics if an exponential curve's scale height isn't positive.
+    pub fn with_curve(height: f32, surface_density: f32, curve: DensityCurve) -> Atmosphere {
+        if let DensityCurve::Exponential { scale_height } = curve {
+            assert!(scale_height > 0., "scale height must be positive");
+        }
+
+        Atmosphere {
+            curve,
+            ..Atmosphere::new(height, surface_density)
+        }
+    }
+
+    /// The density of the air at ``altitude`` above the surface.
+    /// Zero above the atmosphere, and the surface density below the surface.
+    pub fn density_at(&self, altitude: f32) -> f32 {
+        if altitude >= self.height || self.height <= 0. {
+            return 0.;
+        }
+
+        let altitude = altitude.max(0.);
+        let fraction = match self.curve {
+            DensityCurve::Constant => 1.,
+            DensityCurve::Linear => 1. - altitude / self.height,
+            DensityCurve::Exponential { scale_height } => {
+                // The curve is public, so guard against a bad scale height here too.
+                // Too small thins out at once; too large is a straight line.
+                let scale_height = scale_height.max(f32::MIN_POSITIVE);
+                let top = (-self.height / scale_height).exp();
+                if top < 1. {
+                    ((-altitude / scale_height).exp() - top) / (1. - top)
+                } else {
+                    1. - altitude / self.height
+                }
+            },
+            DensityCurve::Quadratic => {
+                let remaining = 1. - altitude / self.height;
+                remaining * remaining
+            },
+        };
+
+        self.surface_density * fraction
+    }
+
+    /// The drag force on ``phys`` moving at ``rel_vel`` relative to the air,
+    /// where the air has ``density``.
+    pub fn drag(density: f32, phys: &EntityPhysics, rel_vel: Vector2f) -> Vector2f {
+        -rel_vel * (0.5 * density * rel_vel.length() * phys.drag_area())
+    }
+}
+
+impl PlanetManager {
+    /// The total density of air at ``pos``, from every planet with an atmosphere.
+    pub fn air_density_at<V: Into<Vector2f>>(&self, pos: V) -> f32 {
+        let pos = pos.into();
+
+        self.planets.iter()
+            .filter_map(|planet| planet.atmosphere().map(|atmosphere| {
+                let altitude = (pos - planet.phys().pos()).length() - planet.radius();
+                atmosphere.density_at(altitude)
+            }))
+            .sum()
+    }
+
+    /// Apply drag from every atmosphere ``phys`` is in, and heat it up.
+    /// The drag is applied as a force, so call this before the update it should
+    /// affect. It never does more than stop the body relative to the air within
+    /// ``dt`` seconds. Returns the total drag force.
+    pub fn apply_atmosphere(&self, phys: &mut EntityPhysics, dt: f32) -> Vector2f {
+        let mut total = Vector2f::new(0., 0.);
+        if phys.is_immovable() {
+            return total;
+        }
+
+        for planet in &self.planets {
+            let atmosphere = match planet.atmosphere() {
+                Some(atmosphere) => atmosphere,
+                None => continue,
+            };
+
+            let altitude = (phys.pos() - planet.phys().pos()).length() - planet.radius();
+            let density = atmosphere.density_at(altitude);
+            if density <= 0. {
+                continue;
+            }
+
+            let rel_vel = phys.vel() - planet.phys().vel();
+            let mut drag = Atmosphere::drag(density, phys, rel_vel);
+
+            // Thick air and a long tick could otherwise flip the body around.
+            let max_drag = phys.mass() * rel_vel.length() / dt;
+            if drag.length() > max_drag {
+                drag = drag.normalize() * max_drag;
+            }
+
+            phys.apply_force(drag);
+            phys.add_heat(-drag.dot(&rel_vel) * dt * atmosphere.heating);
+            total += drag;
+        }
+
+        total
+    }
+
+    /// Apply atmospheric drag and heating to an ``Entity``.
+    /// See ``apply_atmosphere``.
+    pub fn drag_entity<E: Entity + ?Sized>(&self, entity: &mut E, dt: f32) -> Vector2f {
+        self.apply_atmosphere(entity.phys_mut(), dt)
+    }
+}
@@ -0,0 +1,223 @@
+//! Barnes-Hut approximation of gravity from many bodies.
+//!
+//! Bodies are sorted into a quadtree, and each node remembers the total mass and
+//! center of mass of everything below it. When a node is far away compared to
+//! its size, its bodies are treated as one point mass, which makes the force on
+//! one body O(log n) instead of O(n). The opening angle ``theta`` trades
+//! accuracy for speed: ``0`` is exact, ``0.5`` is a common choice, and larger
+//! values are faster but rougher.
+
+use sfml::system::Vector2f;
+use super::gravity::{GravityModel, GravitySource};
+
+// Stop subdividing at this depth so coincident bodies can't recurse forever.
+const MAX_DEPTH: u32 = 24;
+
+/// Which method a ``PlanetManager`` uses to sum gravity.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GravitySolver {
+    /// Add up the pull of every planet exactly. Best for a handful of planets.
+    #[default]
+    Exact,
+
+    /// Approximate with a Barnes-Hut quadtree, using the given opening angle.
+    /// Best for asteroid belts and other large numbers of bodies.
+    BarnesHut {
+        /// The opening angle, ``theta``.
+        theta: f32,
+    },
+}
+
+#[derive(Debug)]
+struct Node {
+    center: Vector2f,
+    half_size: f32,
+    mass: f32,
+    center_of_mass: Vector2f,
+    children: Option<[usize; 4]>,
+    bodies: Vec<usize>,
+}
+
+impl Node {
+    fn new(center: Vector2f, half_size: f32) -> Node {
+        Node {
+            center,
+            half_size,
+            mass: 0.,
+            center_of_mass: center,
+            children: None,
+            bodies: Vec::new(),
+        }
+    }
+}
+
+/// A quadtree of gravity sources, built once per tick and then queried
+/// for every body that needs a force.
+#[derive(Debug)]
+pub struct QuadTree {
+    nodes: Vec<Node>,
+    sources: Vec<GravitySource>,
+}
+
+impl QuadTree {
+    /// Build a tree over ``sources``. Indices into ``sources`` identify bodies
+    /// in ``force_excluding``.
+    pub fn new(sources: Vec<GravitySource>) -> QuadTree {
+        let mut tree = QuadTree {
+            nodes: Vec::new(),
+            sources,
+        };
+
+        if tree.sources.is_empty() {
+            return tree;
+        }
+
+        // The root is the smallest square around every source.
+        let first = tree.sources[0].pos;
+        let (min, max) = tree.sources.iter().fold((first, first), |(min, max), s| {
+            (Vector2f::new(min.x.min(s.pos.x), min.y.min(s.pos.y)),
+             Vector2f::new(max.x.max(s.pos.x), max.y.max(s.pos.y)))
+        });
+        let half_size = ((max.x - min.x).max(max.y - min.y) / 2.).max(1.);
+        tree.nodes.push(Node::new((min + max) / 2., half_size));
+
+        for body in 0..tree.sources.len() {
+            tree.insert(0, body, 0);
+        }
+        tree.summarize(0);
+
+        tree
+    }
+
+    /// Return the number of sources in the tree.
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Return whether the tree has no sources.
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// The approximate force of every source on a body of ``mass`` at ``pos``.
+    pub fn force(&self, gravity: &GravityModel, theta: f32, pos: Vector2f, mass: f32) -> Vector2f {
+        self.force_excluding(gravity, theta, pos, mass, None)
+    }
+
+    /// Like ``force``, but leave out one source, so a body in the tree
+    /// doesn't attract itself.
+    pub fn force_excluding(&self, gravity: &GravityModel, theta: f32, pos: Vector2f, mass: f32,
+                           skip: Option<usize>) -> Vector2f {
+        let mut force = Vector2f::new(0., 0.);
+        self.visit(theta, pos, skip, |source| force += gravity.force(source, pos, mass));
+        force
+    }
+
+    /// The approximate potential energy of a body of ``mass`` at ``pos``.
+    pub fn potential(&self, gravity: &GravityModel, theta: f32, pos: Vector2f, mass: f32) -> f32 {
+        let mut potential = 0.;
+        self.visit(theta, pos, None, |source| potential += gravity.potential(source, pos, mass));
+        potential
+    }
+
+    // Call ``f`` with every source or aggregate that acts on ``pos``. Aggregates
+    // are point masses, so they ignore the radius and influence radius of the
+    // sources inside them.
+    fn visit<F: FnMut(&GravitySource)>(&self, theta: f32, pos: Vector2f, skip: Option<usize>, mut f: F) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+
+            match node.children {
+                Some(children) => {
+                    let difference = node.center_of_mass - pos;
+                    let distance_sq = difference.x * difference.x + difference.y * difference.y;
+                    let size = node.half_size * 2.;
+
+                    // s / d < theta, without the square root. A node that holds the
+                    // skipped body must always be opened, or it would pull on itself.
+                    let contains_skip = skip.is_some_and(|skip| self.contains(node, skip));
+                    if !contains_skip && size * size < theta * theta * distance_sq {
+                        f(&GravitySource::point(node.center_of_mass, node.mass));
+                    } else {
+                        stack.extend_from_slice(&children);
+                    }
+                },
+                None => {
+                    for &body in node.bodies.iter().filter(|&&body| Some(body) != skip) {
+                        f(&self.sources[body]);
+                    }
+                },
+            }
+        }
+    }
+
+    fn contains(&self, node: &Node, body: usize) -> bool {
+        let pos = self.sources[body].pos;
+        (pos.x - node.center.x).abs() <= node.half_size &&
+            (pos.y - node.center.y).abs() <= node.half_size
+    }
+
+    fn insert(&mut self, node: usize, body: usize, depth: u32) {
+        if let Some(children) = self.nodes[node].children {
+            let child = children[self.quadrant(node, body)];
+            self.insert(child, body, depth + 1);
+            return;
+        }
+
+        if self.nodes[node].bodies.is_empty() || depth >= MAX_DEPTH {
+            self.nodes[node].bodies.push(body);
+            return;
+        }
+
+        // An occupied leaf: split it and push both bodies down.
+        let center = self.nodes[node].center;
+        let quarter = self.nodes[node].half_size / 2.;
+        let mut children = [0; 4];
+        for (i, child) in children.iter_mut().enumerate() {
+            let offset = Vector2f::new(if i & 1 == 0 { -quarter } else { quarter },
+                                       if i & 2 == 0 { -quarter } else { quarter });
+            *child = self.nodes.len();
+            self.nodes.push(Node::new(center + offset, quarter));
+        }
+
+        let existing = ::std::mem::take(&mut self.nodes[node].bodies);
+        self.nodes[node].children = Some(children);
+        for other in existing {
+            self.insert(node, other, depth);
+        }
+        self.insert(node, body, depth);
+    }
+
+    fn quadrant(&self, node: usize, body: usize) -> usize {
+        let center = self.nodes[node].center;
+        let pos = self.sources[body].pos;
+        (if pos.x < center.x { 0 } else { 1 }) | (if pos.y < center.y { 0 } else { 2 })
+    }
+
+    // Fill in mass and center of mass from the leaves up.
+    fn summarize(&mut self, node: usize) -> (f32, Vector2f) {
+        let (mass, weighted) = match self.nodes[node].children {
+            Some(children) => children.iter().fold((0., Vector2f::new(0., 0.)), |(m, w), &child| {
+                let (child_mass, child_com) = self.summarize(child);
+                (m + child_mass, w + child_com * child_mass)
+            }),
+            None => self.nodes[node].bodies.iter().fold((0., Vector2f::new(0., 0.)), |(m, w), &b| {
+                let source = &self.sources[b];
+                (m + source.mass, w + source.pos * source.mass)
+            }),
+        };
+
+        let node = &mut self.nodes[node];
+        node.mass = mass;
+        if mass > 0. {
+            node.center_of_mass = weighted / mass;
+        }
+
+        (node.mass, node.center_of_mass)
+    }
+}
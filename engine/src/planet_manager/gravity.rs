@@ -0,0 +1,255 @@
+//! The law of gravity used by a ``PlanetManager``.
+
+use std::fs;
+use std::str::FromStr;
+use sfml::system::Vector2f;
+use util::Vector2Ext;
+
+/// The gravitational constant used unless a ``GravityModel`` says otherwise.
+/// Tuned so that gravity feels the same as it did with the old per-tick
+/// integration at 45 ticks per second (0.5 * 45 * 45).
+pub const DEFAULT_GRAVITATIONAL_CONSTANT: f32 = 1012.5;
+
+/// The softening length used unless a ``GravityModel`` says otherwise.
+pub const DEFAULT_SOFTENING: f32 = 10.;
+
+/// Tunable parameters of gravity. Each ``PlanetManager`` has its own,
+/// so different star systems can feel different.
+///
+/// The force on a body of mass ``m`` at distance ``r`` from a planet of mass ``M`` is
+///
+/// ```text
+///         G M m r
+/// F = ------------------
+///     (r² + ε²)^((n+1)/2)
+/// ```
+///
+/// where ``ε`` is the softening length and ``n`` the falloff exponent.
+/// With ``ε = 0`` and ``n = 2`` this is Newton's inverse-square law; softening
+/// smoothly weakens gravity very close to the center instead of letting it
+/// blow up, so nothing ever gets a sudden jolt.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GravityModel {
+    /// The gravitational constant ``G``.
+    pub constant: f32,
+
+    /// The Plummer softening length ``ε``.
+    pub softening: f32,
+
+    /// The falloff exponent ``n``. ``2`` is inverse-square.
+    pub falloff: f32,
+
+    /// If set, gravity inside a planet's surface falls off linearly towards the
+    /// center, like a uniform solid ball, instead of growing. Only matters for
+    /// things that can get inside a planet's radius.
+    pub surface_gravity: bool,
+}
+
+impl GravityModel {
+    /// Read a model from a file of ``key = value`` lines. See ``from_str``.
+    pub fn from_file(path: &str) -> Result<GravityModel, String> {
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        source.parse().map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+impl Default for GravityModel {
+    fn default() -> GravityModel {
+        GravityModel {
+            constant: DEFAULT_GRAVITATIONAL_CONSTANT,
+            softening: DEFAULT_SOFTENING,
+            falloff: 2.,
+            surface_gravity: false,
+        }
+    }
+}
+
+/// A body that attracts others.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GravitySource {
+    /// Position of the center of mass.
+    pub pos: Vector2f,
+
+    /// Mass of the body.
+    pub mass: f32,
+
+    /// Radius of the body's surface, used by ``GravityModel::surface_gravity``.
+    pub radius: f32,
+
+    /// Beyond this distance the body exerts no force at all.
+    pub influence_radius: Option<f32>,
+}
+
+impl GravitySource {
+    /// A point mass with no surface and unlimited reach.
+    pub fn point(pos: Vector2f, mass: f32) -> GravitySource {
+        GravitySource {
+            pos,
+            mass,
+            radius: 0.,
+            influence_radius: None,
+        }
+    }
+}
+
+impl GravityModel {
+    /// The force ``source`` exerts on a body of ``mass`` at ``pos``.
+    pub fn force(&self, source: &GravitySource, pos: Vector2f, mass: f32) -> Vector2f {
+        let difference = source.pos - pos;
+        let distance_sq = difference.length_sq();
+
+        if let Some(influence_radius) = source.influence_radius {
+            if distance_sq > influence_radius * influence_radius {
+                return Vector2f::new(0., 0.);
+            }
+        }
+
+        // Inside the surface, use the strength at the surface scaled by depth.
+        let eval_sq = if self.surface_gravity {
+            distance_sq.max(source.radius * source.radius)
+        } else {
+            distance_sq
+        };
+
+        let denominator = (eval_sq + self.softening * self.softening).powf((self.falloff + 1.) / 2.);
+        if denominator == 0. {
+            return Vector2f::new(0., 0.);
+        }
+
+        difference * (self.constant * source.mass * mass / denominator)
+    }
+
+    /// The potential energy of a body of ``mass`` at ``pos`` due to ``source``.
+    /// Its negative gradient is ``force``.
+    pub fn potential(&self, source: &GravitySource, pos: Vector2f, mass: f32) -> f32 {
+        let mut distance_sq = (source.pos - pos).length_sq();
+
+        // No force beyond the influence radius means the potential is flat there.
+        if let Some(influence_radius) = source.influence_radius {
+            distance_sq = distance_sq.min(influence_radius * influence_radius);
+        }
+
+        let gmm = self.constant * source.mass * mass;
+        let radius_sq = source.radius * source.radius;
+
+        if self.surface_gravity && distance_sq < radius_sq {
+            let strength = gmm / (radius_sq + self.softening * self.softening)
+                .powf((self.falloff + 1.) / 2.);
+            self.point_potential(gmm, radius_sq) + strength / 2. * (distance_sq - radius_sq)
+        } else {
+            self.point_potential(gmm, distance_sq)
+        }
+    }
+
+    fn point_potential(&self, gmm: f32, distance_sq: f32) -> f32 {
+        let softened_sq = distance_sq + self.softening * self.softening;
+
+        if (self.falloff - 1.).abs() < 1e-6 {
+            gmm / 2. * softened_sq.ln()
+        } else {
+            -gmm / (self.falloff - 1.) * softened_sq.powf(-(self.falloff - 1.) / 2.)
+        }
+    }
+}
+
+impl FromStr for GravityModel {
+    type Err = String;
+
+    /// Read a model from ``key = value`` lines, so it can live in a data file.
+    /// Keys are ``constant``, ``softening``, ``falloff`` and ``surface_gravity``.
+    /// Missing keys keep their defaults, and ``#`` starts a comment.
+    /// Numbers must be finite and can't be negative.
+    fn from_str(s: &str) -> Result<GravityModel, String> {
+        let mut model = GravityModel::default();
+
+        for (number, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=').map(str::trim);
+            let key = parts.next().unwrap();
+            let value = parts.next()
+                .ok_or_else(|| format!("line {}: expected `key = value`", number + 1))?;
+
+            match key {
+                "constant" => model.constant = parse_number(number + 1, key, value)?,
+                "softening" => model.softening = parse_number(number + 1, key, value)?,
+                "falloff" => model.falloff = parse_number(number + 1, key, value)?,
+                "surface_gravity" => model.surface_gravity = parse_value(number + 1, key, value)?,
+                _ => return Err(format!("line {}: unknown key `{}`", number + 1, key)),
+            }
+        }
+
+        Ok(model)
+    }
+}
+
+// Parse the value of a ``key = value`` line, whatever its type.
+fn parse_value<T: FromStr>(line: usize, key: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("line {}: bad value for `{}`: {}", line, key, value))
+}
+
+// Parse a number that is neither negative nor NaN nor infinite, any of
+// which would make every force NaN or send things flying.
+fn parse_number(line: usize, key: &str, value: &str) -> Result<f32, String> {
+    match parse_value::<f32>(line, key, value)? {
+        number if number >= 0. && number.is_finite() => Ok(number),
+        _ => Err(format!("line {}: `{}` must be finite and not negative: {}", line, key, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GravityModel, DEFAULT_GRAVITATIONAL_CONSTANT};
+
+    fn error(source: &str) -> String {
+        source.parse::<GravityModel>().unwrap_err()
+    }
+
+    #[test]
+    fn reads_every_key() {
+        let model: GravityModel = "\
+# Weak, short range gravity.
+constant = 500
+softening=2.5
+falloff = 3  # faster than inverse-square
+
+surface_gravity = true
+".parse().unwrap();
+
+        assert_eq!(model, GravityModel {
+            constant: 500.,
+            softening: 2.5,
+            falloff: 3.,
+            surface_gravity: true,
+        });
+    }
+
+    #[test]
+    fn missing_keys_keep_defaults() {
+        let model: GravityModel = "falloff = 1.5".parse().unwrap();
+        assert_eq!(model.constant, DEFAULT_GRAVITATIONAL_CONSTANT);
+        assert_eq!(model.falloff, 1.5);
+        assert_eq!("".parse::<GravityModel>().unwrap(), GravityModel::default());
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        assert_eq!(error("constant = 1\nsoftening"), "line 2: expected `key = value`");
+        assert_eq!(error("mass = 1"), "line 1: unknown key `mass`");
+        assert_eq!(error("falloff = two"), "line 1: bad value for `falloff`: two");
+        assert_eq!(error("surface_gravity = 1"), "line 1: bad value for `surface_gravity`: 1");
+    }
+
+    #[test]
+    fn rejects_numbers_out_of_range() {
+        assert_eq!(error("constant = -1"),
+                   "line 1: `constant` must be finite and not negative: -1");
+        assert_eq!(error("softening = nan"),
+                   "line 1: `softening` must be finite and not negative: nan");
+        assert_eq!(error("falloff = inf"),
+                   "line 1: `falloff` must be finite and not negative: inf");
+    }
+}
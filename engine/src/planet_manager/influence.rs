@@ -0,0 +1,177 @@
+//! Spheres of influence: which planet "owns" each region of space.
+//!
+//! Each planet's parent is the heavier planet that pulls on it hardest, such as
+//! the star a planet orbits or the planet a moon orbits. Planets on rails around
+//! another planet always have that planet as their parent. A planet's sphere of
+//! influence is the region around it where its own gravity matters more than
+//! its parent's. The topmost planets have no parent and reach everywhere.
+
+use sfml::system::Vector2f;
+use util::Vector2Ext;
+use super::{Anchor, PlanetManager, PlanetMotion};
+
+// How far a planet can move, as a fraction of the distance to its nearest
+// neighbour, before parents are worked out again.
+const PARENT_RECHECK_FRACTION: f32 = 0.1;
+
+/// A change of dominant planet reported by a ``SoiTracker``.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SoiEvent {
+    /// Left the sphere of influence of a planet.
+    Exited(usize),
+
+    /// Entered the sphere of influence of a planet.
+    Entered(usize),
+}
+
+/// Remembers which planet dominates one entity, and reports when that changes.
+/// Keep one per entity that cares, such as the player's ship.
+#[derive(Clone, Debug, Default)]
+pub struct SoiTracker {
+    current: Option<usize>,
+}
+
+impl SoiTracker {
+    /// Create a tracker that isn't in any sphere of influence yet.
+    pub fn new() -> SoiTracker {
+        Default::default()
+    }
+
+    /// The planet dominating the entity as of the last ``update``.
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Check the dominant planet at ``pos``, returning the transitions since the
+    /// last update. Leaving one sphere for another gives an ``Exited`` followed
+    /// by an ``Entered``.
+    pub fn update<V: Into<Vector2f>>(&mut self, manager: &PlanetManager, pos: V) -> Vec<SoiEvent> {
+        let dominant = manager.dominant_body(pos);
+        if dominant == self.current {
+            return Vec::new();
+        }
+
+        let events = self.current.map(SoiEvent::Exited).into_iter()
+            .chain(dominant.map(SoiEvent::Entered))
+            .collect();
+        self.current = dominant;
+        events
+    }
+}
+
+impl PlanetManager {
+    /// The planet that a planet orbits, if any. See the module documentation.
+    /// Parents are worked out when planets are added, and again on ``update``
+    /// once any planet has moved a tenth of the way to its nearest neighbour,
+    /// so they can lag slightly behind a close pass.
+    pub fn parent_of(&self, planet: usize) -> Option<usize> {
+        self.parents[planet]
+    }
+
+    // Work out every planet's parent from scratch if planets were added or
+    // have moved far enough to matter. Checking is linear; working them out
+    // is quadratic, so it shouldn't happen every tick.
+    pub(super) fn update_parents(&mut self) {
+        let moved = self.parent_checks.len() != self.planets.len()
+            || self.parent_checks.iter().enumerate().any(|(i, &(pos, slack))| {
+                (self.pos_mass(i).0 - pos).length_sq() > slack * slack
+            });
+        if !moved {
+            return;
+        }
+
+        self.parents = (0..self.planets.len()).map(|i| self.find_parent(i)).collect();
+        self.parent_checks = (0..self.planets.len())
+            .map(|i| (self.pos_mass(i).0, PARENT_RECHECK_FRACTION * self.nearest_distance(i)))
+            .collect();
+    }
+
+    // The distance to the closest other planet, or infinity if there are none.
+    fn nearest_distance(&self, planet: usize) -> f32 {
+        let pos = self.pos_mass(planet).0;
+        (0..self.planets.len())
+            .filter(|&other| other != planet)
+            .map(|other| (self.pos_mass(other).0 - pos).length_sq())
+            .fold(f32::INFINITY, f32::min)
+            .sqrt()
+    }
+
+    fn find_parent(&self, planet: usize) -> Option<usize> {
+        if let PlanetMotion::OnRails(ref rails) = self.planets[planet].motion {
+            if let Anchor::Planet(anchor) = rails.anchor() {
+                return Some(anchor);
+            }
+        }
+
+        let (pos, mass) = self.pos_mass(planet);
+
+        (0..self.planets.len())
+            .filter(|&other| other != planet && self.pos_mass(other).1 > mass)
+            .map(|other| {
+                let (other_pos, other_mass) = self.pos_mass(other);
+                (other, other_mass / (other_pos - pos).length_sq())
+            })
+            .fold(None, |best: Option<(usize, f32)>, (other, pull)| match best {
+                Some((_, best_pull)) if best_pull >= pull => best,
+                _ => Some((other, pull)),
+            })
+            .map(|(other, _)| other)
+    }
+
+    /// The Laplace sphere-of-influence radius of a planet, ``a (m / M)^(2/5)``, where
+    /// ``a`` is the distance to its parent. ``None`` means it has no parent and
+    /// its influence is unbounded.
+    pub fn soi_radius(&self, planet: usize) -> Option<f32> {
+        self.parent_of(planet).map(|parent| {
+            let (pos, mass) = self.pos_mass(planet);
+            let (parent_pos, parent_mass) = self.pos_mass(parent);
+            (parent_pos - pos).length() * (mass / parent_mass).powf(0.4)
+        })
+    }
+
+    /// The Hill sphere radius of a planet, ``a (m / 3M)^(1/3)``: roughly how far
+    /// out moons can stably orbit it. ``None`` when it has no parent.
+    pub fn hill_radius(&self, planet: usize) -> Option<f32> {
+        self.parent_of(planet).map(|parent| {
+            let (pos, mass) = self.pos_mass(planet);
+            let (parent_pos, parent_mass) = self.pos_mass(parent);
+            (parent_pos - pos).length() * (mass / (3. * parent_mass)).cbrt()
+        })
+    }
+
+    /// The planet whose gravity dominates at ``pos``: the one with the smallest
+    /// sphere of influence containing it. Ties, such as between several planets
+    /// with unbounded influence, go to the one pulling hardest at ``pos``.
+    pub fn dominant_body<V: Into<Vector2f>>(&self, pos: V) -> Option<usize> {
+        let pos = pos.into();
+        let mut best: Option<(usize, f32, f32)> = None;
+
+        for planet in 0..self.planets.len() {
+            let (planet_pos, mass) = self.pos_mass(planet);
+            let distance_sq = (planet_pos - pos).length_sq();
+            let soi = self.soi_radius(planet).unwrap_or(f32::INFINITY);
+            if distance_sq > soi * soi {
+                continue;
+            }
+
+            let pull = mass / distance_sq;
+            let better = match best {
+                Some((_, best_soi, best_pull)) => {
+                    soi < best_soi || (soi == best_soi && pull > best_pull)
+                },
+                None => true,
+            };
+
+            if better {
+                best = Some((planet, soi, pull));
+            }
+        }
+
+        best.map(|(planet, _, _)| planet)
+    }
+
+    fn pos_mass(&self, planet: usize) -> (Vector2f, f32) {
+        let phys = self.planets[planet].phys();
+        (phys.pos(), phys.mass())
+    }
+}
@@ -0,0 +1,586 @@
+//! This module provides support for large space bodies that are renderable
+//! and induce a large gravitational force.
+//!
+//! Planets are fixed in place by default. Individual planets can instead be
+//! made free, so they move under the gravity of every other planet (N-body),
+//! or put on rails, so they follow a prescribed orbit exactly.
+//!
+//! Gravity is summed exactly by default. Systems with many bodies, such as
+//! asteroid belts, can switch to a Barnes-Hut approximation with ``set_solver``.
+//! Planets can also have an ``Atmosphere`` that slows down and heats up
+//! anything flying through it.
+
+pub use self::atmosphere::{Atmosphere, DensityCurve};
+pub use self::barnes_hut::{GravitySolver, QuadTree};
+pub use self::gravity::{GravityModel, GravitySource};
+pub use self::influence::{SoiEvent, SoiTracker};
+pub use self::orbit::{gravitational_parameter, OrbitDirection, OrbitalElements};
+pub use self::rails::{Anchor, Rails};
+pub use self::trajectory::{Impact, Trajectory, TrajectoryLine};
+
+pub mod atmosphere;
+pub mod barnes_hut;
+pub mod gravity;
+pub mod influence;
+pub mod orbit;
+pub mod rails;
+pub mod trajectory;
+
+use sfml::graphics::{Drawable, RenderStates, RenderTarget};
+use sfml::system::Vector2f;
+use checksum::{SimState, StateHasher};
+use collision::{self, ccd, query, Bounds, Collider, CollisionFilter, Contact, Hit, Material,
+                Ray, Shape, SpatialHash, Sweep, WorldShape};
+use entity::{Entity, EntityPhysics, ForceField, NoField, SpriteEntity};
+use refcounted::RcTexture;
+use util::Vector2Ext;
+
+// Planets are large, so use coarse cells when indexing them.
+const PLANET_CELL_SIZE: f32 = 512.;
+
+/// Drawable collection of planets.
+pub struct PlanetManager {
+    planets: Vec<Planet>,
+    index: SpatialHash<usize>,
+    time: f64,
+    gravity: GravityModel,
+    parents: Vec<Option<usize>>,
+    // Where each planet was when parents were last worked out, and how far it
+    // can move from there before they need working out again.
+    parent_checks: Vec<(Vector2f, f32)>,
+    solver: GravitySolver,
+    tree: QuadTree,
+}
+
+impl PlanetManager {
+    /// Create a ``PlanetManager`` with no planets, using the default ``GravityModel``.
+    pub fn new() -> PlanetManager {
+        Self::with_gravity(Default::default())
+    }
+
+    /// Create a ``PlanetManager`` with no planets and a custom ``GravityModel``.
+    pub fn with_gravity(gravity: GravityModel) -> PlanetManager {
+        PlanetManager {
+            planets: Vec::new(),
+            index: SpatialHash::new(PLANET_CELL_SIZE),
+            time: 0.,
+            gravity,
+            parents: Vec::new(),
+            parent_checks: Vec::new(),
+            solver: GravitySolver::Exact,
+            tree: QuadTree::new(Vec::new()),
+        }
+    }
+
+    /// Replace the ``GravityModel``.
+    pub fn set_gravity(&mut self, gravity: GravityModel) {
+        self.gravity = gravity;
+    }
+
+    /// Access the ``GravityModel``.
+    pub fn gravity(&self) -> &GravityModel {
+        &self.gravity
+    }
+
+    /// Choose how gravity is summed. See ``GravitySolver``.
+    pub fn set_solver(&mut self, solver: GravitySolver) {
+        self.solver = solver;
+        self.rebuild_tree();
+    }
+
+    /// Return how gravity is summed.
+    pub fn solver(&self) -> GravitySolver {
+        self.solver
+    }
+
+    /// The force of every planet on a body of ``mass`` at ``pos``,
+    /// summed exactly whatever the solver.
+    pub fn exact_force_at(&self, pos: Vector2f, mass: f32) -> Vector2f {
+        self.planets.iter().fold(Vector2f::new(0., 0.), |force, i| {
+            force + self.gravity.force(&i.gravity_source(), pos, mass)
+        })
+    }
+
+    /// How far the solver's force at ``pos`` is from the exact force, relative
+    /// to the exact force. Handy for picking a ``theta`` that is accurate enough.
+    pub fn gravity_error(&self, pos: Vector2f, mass: f32) -> f32 {
+        let exact = self.exact_force_at(pos, mass);
+        let error = (self.force_at(pos, mass) - exact).length();
+        let magnitude = exact.length();
+
+        if magnitude > 0. { error / magnitude } else { error }
+    }
+
+    /// Add a planet to the manager, transferring ownership.
+    /// Returns the planet's index, which other planets can orbit with ``Anchor::Planet``.
+    pub fn add_planet(&mut self, planet: Planet) -> usize {
+        let index = self.planets.len();
+
+        if let PlanetMotion::OnRails(ref rails) = planet.motion {
+            if let Anchor::Planet(anchor) = rails.anchor() {
+                assert!(anchor < index, "a planet on rails must be added after its anchor");
+            }
+        }
+
+        self.index.insert(index, planet.collider().bounds(planet.phys()));
+        self.planets.push(planet);
+        self.update_parents();
+        self.rebuild_tree();
+        index
+    }
+
+    /// Access all planets, in the order they were added.
+    pub fn planets(&self) -> &[Planet] {
+        &self.planets
+    }
+
+    /// Access a planet by index.
+    pub fn planet(&self, index: usize) -> Option<&Planet> {
+        self.planets.get(index)
+    }
+
+    /// Simulate planet motion for ``dt`` seconds. Free planets are pulled by
+    /// all the others, planets on rails advance along their orbits, and fixed
+    /// planets stay put. Call this once per tick, before updating entities.
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt as f64;
+
+        // Every free planet feels the others where they were at the start of the tick.
+        let sources: Vec<_> = self.planets.iter().map(Planet::gravity_source).collect();
+
+        for i in 0..self.planets.len() {
+            match self.planets[i].motion.clone() {
+                PlanetMotion::Fixed => {
+                    self.planets[i].entity.update(dt, &NoField);
+                },
+                PlanetMotion::Free => {
+                    let field = PointMasses {
+                        sources: &sources,
+                        gravity: &self.gravity,
+                        solver: self.solver,
+                        tree: &self.tree,
+                        skip: i,
+                    };
+                    self.planets[i].entity.update(dt, &field);
+                },
+                PlanetMotion::OnRails(rails) => {
+                    // Anchors always come first, so they have already moved this tick.
+                    let (anchor_pos, anchor_vel) = match rails.anchor() {
+                        Anchor::Point(point) => (point, Vector2f::new(0., 0.)),
+                        Anchor::Planet(anchor) => {
+                            let phys = self.planets[anchor].phys();
+                            (phys.pos(), phys.vel())
+                        },
+                    };
+
+                    let (offset, rel_vel) = rails.state_at(self.time);
+                    let pos = anchor_pos + offset;
+                    let vel = anchor_vel + rel_vel;
+
+                    let entity = &mut self.planets[i].entity;
+                    entity.update(dt, &NoField);
+                    let offset = pos - entity.phys().pos();
+                    entity.phys_mut().translate(offset);
+                    entity.phys_mut().set_vel(vel);
+                },
+            }
+
+            if self.planets[i].motion != PlanetMotion::Fixed {
+                self.reindex(i);
+            }
+        }
+
+        self.update_parents();
+        self.rebuild_tree();
+    }
+
+    /// The simulated time in seconds, the total of every ``update``'s ``dt``.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Access the spatial index of planet colliders, keyed by planet index.
+    pub fn index(&self) -> &SpatialHash<usize> {
+        &self.index
+    }
+
+    /// Blend every planet's sprite between its previous and current tick.
+    /// See ``SpriteEntity::interpolate``.
+    pub fn interpolate(&mut self, alpha: f32) {
+        for i in &mut self.planets {
+            i.entity.interpolate(alpha);
+        }
+    }
+
+    /// Find every planet that an ``Entity`` is touching.
+    /// Returns the index of each planet along with the contact, whose normal
+    /// points from the entity towards the planet.
+    /// Entities without a collider never touch anything.
+    pub fn contacts<E: Entity + ?Sized>(&self, entity: &E) -> Vec<(usize, Contact)> {
+        let collider = match entity.collider() {
+            Some(collider) => collider,
+            None => return Vec::new(),
+        };
+        let shape = collider.world_shape(entity.phys());
+
+        self.index.query_region(&shape.bounds())
+            .into_iter()
+            .filter(|&i| collider.filter().collides_with(&self.planets[i].collider().filter()))
+            .filter_map(|i| {
+                let planet = &self.planets[i];
+                let planet_shape = planet.collider().world_shape(planet.phys());
+                collision::collide(&shape, &planet_shape).map(|contact| (i, contact))
+            })
+            .collect()
+    }
+
+    /// Resolve collisions between an ``Entity`` and every planet it touches,
+    /// bouncing it off their surfaces. Returns the contacts that were resolved.
+    /// Bullets are swept through their last update and moved back to the
+    /// first planet they hit, so they can't pass through. A bullet already
+    /// touching at the start, such as one resting on the surface, is left
+    /// where it is for the usual resolution.
+    pub fn collide_entity<E: Entity + ?Sized>(&mut self, entity: &mut E) -> Vec<(usize, Contact)> {
+        if entity.phys().is_bullet() {
+            match self.time_of_impact(entity) {
+                Some(toi) if toi > 0. => ccd::rewind(entity.phys_mut(), toi),
+                _ => {},
+            }
+        }
+
+        let contacts = self.contacts(entity);
+
+        for &(i, ref contact) in &contacts {
+            let entity_material = *entity.collider().unwrap().material();
+            let planet_material = *self.planets[i].collider().material();
+            collision::resolve(entity.phys_mut(), &entity_material,
+                               self.planets[i].entity.phys_mut(), &planet_material, contact);
+
+            // The planet may have been pushed, and later queries this tick should see it.
+            self.reindex(i);
+        }
+
+        if !contacts.is_empty() {
+            self.rebuild_tree();
+        }
+
+        contacts
+    }
+
+    /// The fraction of its last update at which an ``Entity`` first touched a
+    /// planet, treating planets as staying where they are now. ``None`` if it
+    /// touched nothing or has no collider. See ``collision::time_of_impact``.
+    pub fn time_of_impact<E: Entity + ?Sized>(&self, entity: &E) -> Option<f32> {
+        let collider = entity.collider()?;
+        let sweep = Sweep::of(entity.phys());
+
+        self.index.query_region(&collision::swept_bounds(collider, &sweep))
+            .into_iter()
+            .filter(|&i| collider.filter().collides_with(&self.planets[i].collider().filter()))
+            .filter_map(|i| {
+                let planet = self.planets[i].phys();
+                collision::time_of_impact(collider, &sweep, self.planets[i].collider(),
+                                          &Sweep::fixed(planet.pos(), planet.rot()))
+            })
+            .fold(None, |earliest: Option<f32>, toi| Some(earliest.map_or(toi, |e| e.min(toi))))
+    }
+
+    /// The nearest planet a ray hits, if any. See ``collision::query``.
+    pub fn raycast(&self, ray: &Ray, filter: &CollisionFilter) -> Option<(usize, Hit)> {
+        query::nearest(self.raycast_all(ray, filter))
+    }
+
+    /// Every planet a ray hits, nearest first.
+    pub fn raycast_all(&self, ray: &Ray, filter: &CollisionFilter) -> Vec<(usize, Hit)> {
+        let mut hits: Vec<_> = self.query_candidates(&ray.bounds(), filter)
+            .filter_map(|(i, shape)| query::raycast(ray, &shape).map(|hit| (i, hit)))
+            .collect();
+        query::sort_hits(&mut hits);
+        hits
+    }
+
+    /// The first planet a collider touches as it moves along a sweep, using
+    /// the collider's own filter.
+    pub fn shape_cast(&self, collider: &Collider, sweep: &Sweep) -> Option<(usize, Hit)> {
+        let region = collision::swept_bounds(collider, sweep);
+        query::nearest(self.query_candidates(&region, &collider.filter())
+            .filter_map(|(i, shape)| query::shape_cast(collider, sweep, &shape).map(|hit| (i, hit))))
+    }
+
+    /// Every planet containing a point.
+    pub fn overlap_point<V: Into<Vector2f>>(&self, point: V, filter: &CollisionFilter)
+                                            -> Vec<usize> {
+        let point = point.into();
+        self.query_candidates(&Bounds::new(point, point), filter)
+            .filter(|(_, shape)| shape.contains(point))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Every planet overlapping an area.
+    pub fn overlap_shape(&self, area: &WorldShape, filter: &CollisionFilter) -> Vec<usize> {
+        self.query_candidates(&area.bounds(), filter)
+            .filter(|(_, shape)| collision::collide(area, shape).is_some())
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Apply gravity of planets to an ``Entity``.
+    /// The force is sampled once at the entity's current position and held
+    /// constant over the next update. For better accuracy with higher order
+    /// integrators, pass the ``PlanetManager`` as the entity's ``ForceField``
+    /// instead of calling this.
+    pub fn affect_entity<E: Entity + ?Sized>(&self, entity: &mut E) {
+        let entity_phys = entity.phys_mut();
+        let force = self.force_at(entity_phys.pos(), entity_phys.mass());
+        entity_phys.apply_force(force);
+
+        /*
+        let entity_phys = entity.phys_mut();
+        let entity_pos = entity_phys.pos();
+        let entity_mass = entity_phys.mass();
+
+        for i in &self.planets {
+            let difference = i.entity.phys().pos() - entity_pos;
+            let distance_sq = difference.length_sq();
+            let magnitude = (i.entity.phys().mass() * entity_mass) / distance_sq;
+            if !magnitude.is_infinite() {
+                entity_phys.apply_force(difference.normalize() * magnitude * 1.0e-2);
+            }
+            //println!("magnitude: {:?}", magnitude);
+
+            //     Gmm
+            // F = ---
+            //     r^2
+            //
+
+            //phys.apply_force();
+        }
+        */
+    }
+
+    // Planets that might touch ``region`` and pass the filter, with their shapes, in index order.
+    fn query_candidates<'a>(&'a self, region: &Bounds, filter: &'a CollisionFilter)
+                            -> impl Iterator<Item = (usize, WorldShape)> + 'a {
+        let mut candidates = if query::is_small_region(self.index.cell_size(), region) {
+            self.index.query_region(region)
+        } else {
+            (0..self.planets.len()).collect()
+        };
+        candidates.sort_unstable();
+
+        candidates.into_iter()
+            .filter(move |&i| filter.accepts(&self.planets[i].collider().filter()))
+            .map(move |i| (i, self.planets[i].collider().world_shape(self.planets[i].phys())))
+    }
+
+    // Move a planet to where it is now in the spatial index.
+    fn reindex(&mut self, planet: usize) {
+        let bounds = self.planets[planet].collider().bounds(self.planets[planet].phys());
+        self.index.update(planet, bounds);
+    }
+
+    // The tree is only kept up to date while it is in use.
+    fn rebuild_tree(&mut self) {
+        let sources = match self.solver {
+            GravitySolver::Exact => Vec::new(),
+            GravitySolver::BarnesHut { .. } => self.planets.iter().map(Planet::gravity_source).collect(),
+        };
+        self.tree = QuadTree::new(sources);
+    }
+}
+
+impl ForceField for PlanetManager {
+    fn force_at(&self, pos: Vector2f, mass: f32) -> Vector2f {
+        match self.solver {
+            GravitySolver::Exact => self.exact_force_at(pos, mass),
+            GravitySolver::BarnesHut { theta } => self.tree.force(&self.gravity, theta, pos, mass),
+        }
+    }
+
+    fn potential_at(&self, pos: Vector2f, mass: f32) -> f32 {
+        match self.solver {
+            GravitySolver::Exact => {
+                self.planets.iter()
+                    .map(|i| self.gravity.potential(&i.gravity_source(), pos, mass))
+                    .sum()
+            },
+            GravitySolver::BarnesHut { theta } => self.tree.potential(&self.gravity, theta, pos, mass),
+        }
+    }
+}
+
+// A snapshot of planets, used to pull free planets towards each other
+// without a planet attracting itself.
+struct PointMasses<'a> {
+    sources: &'a [GravitySource],
+    gravity: &'a GravityModel,
+    solver: GravitySolver,
+    tree: &'a QuadTree,
+    skip: usize,
+}
+
+impl<'a> ForceField for PointMasses<'a> {
+    fn force_at(&self, pos: Vector2f, mass: f32) -> Vector2f {
+        // The tree was built at the end of the last tick, so it matches the snapshot.
+        if let GravitySolver::BarnesHut { theta } = self.solver {
+            return self.tree.force_excluding(self.gravity, theta, pos, mass, Some(self.skip));
+        }
+
+        self.sources.iter()
+            .enumerate()
+            .filter(|&(i, _)| i != self.skip)
+            .fold(Vector2f::new(0., 0.), |force, (_, source)| {
+                force + self.gravity.force(source, pos, mass)
+            })
+    }
+}
+
+impl Drawable for PlanetManager {
+    fn draw<'a: 'shader, 'texture, 'shader, 'shader_texture> (
+            &'a self,
+            target: &mut RenderTarget,
+            states: RenderStates<'texture, 'shader, 'shader_texture>) {
+        for i in &self.planets {
+            let states = RenderStates {
+                blend_mode: states.blend_mode,
+                transform: states.transform,
+                texture: states.texture,
+                shader: states.shader,
+            };
+
+            target.draw_with_renderstates(&i.entity, states);
+        }
+    }
+}
+
+// Planets are rocky: they barely bounce and have plenty of grip.
+const PLANET_RESTITUTION: f32 = 0.1;
+const PLANET_FRICTION: f32 = 0.6;
+
+/// How a planet moves.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlanetMotion {
+    /// Never moves. This is the default.
+    Fixed,
+
+    /// Moves under the gravity of every other planet in the manager.
+    /// Free planets can also be pushed around by collisions.
+    Free,
+
+    /// Follows a prescribed orbit exactly, ignoring gravity and collisions.
+    OnRails(Rails),
+}
+
+/// Represents an individual planet.
+pub struct Planet {
+    entity: SpriteEntity,
+    motion: PlanetMotion,
+    influence_radius: Option<f32>,
+    atmosphere: Option<Atmosphere>,
+}
+
+impl Planet {
+    /// Create a new planet with a texture, mass, and position in world coordinates.
+    /// The planet's collider is a circle that fits the width of the texture.
+    /// Planets start out fixed and immovable: ships bounce off them, not the other way around.
+    pub fn new<V: Into<Vector2f>>(texture: RcTexture, mass: f32, pos: V) -> Planet {
+        let radius = texture.size().x as f32 / 2.;
+        let mut phys = EntityPhysics::with_damping_pos(mass, 1., 0., 0., pos);
+        phys.set_immovable(true);
+
+        let mut collider = Collider::new(Shape::circle(radius));
+        collider.set_material(Material::new(PLANET_RESTITUTION, PLANET_FRICTION));
+
+        let mut entity = SpriteEntity::with_texture_phys(texture, phys);
+        entity.set_collider(Some(collider));
+
+        Planet {
+            entity,
+            motion: PlanetMotion::Fixed,
+            influence_radius: None,
+            atmosphere: None,
+        }
+    }
+
+    /// Limit how far the planet's gravity reaches. ``None``, the default,
+    /// means it reaches everywhere.
+    pub fn set_influence_radius(&mut self, influence_radius: Option<f32>) {
+        self.influence_radius = influence_radius;
+    }
+
+    /// Return how far the planet's gravity reaches.
+    pub fn influence_radius(&self) -> Option<f32> {
+        self.influence_radius
+    }
+
+    /// Give the planet an atmosphere, or take it away with ``None``.
+    pub fn set_atmosphere(&mut self, atmosphere: Option<Atmosphere>) {
+        self.atmosphere = atmosphere;
+    }
+
+    /// Access the planet's atmosphere, if it has one.
+    pub fn atmosphere(&self) -> Option<&Atmosphere> {
+        self.atmosphere.as_ref()
+    }
+
+    /// Describe the planet as a source of gravity.
+    pub fn gravity_source(&self) -> GravitySource {
+        GravitySource {
+            pos: self.phys().pos(),
+            mass: self.phys().mass(),
+            radius: self.radius(),
+            influence_radius: self.influence_radius,
+        }
+    }
+
+    /// Change how the planet moves. Only free planets react to collisions.
+    /// Give a free planet a starting velocity with ``phys_mut().set_vel()``.
+    pub fn set_motion(&mut self, motion: PlanetMotion) {
+        self.entity.phys_mut().set_immovable(motion != PlanetMotion::Free);
+        self.motion = motion;
+    }
+
+    /// Return how the planet moves.
+    pub fn motion(&self) -> &PlanetMotion {
+        &self.motion
+    }
+
+    /// Mutably access the planet's physics component.
+    pub fn phys_mut(&mut self) -> &mut EntityPhysics {
+        self.entity.phys_mut()
+    }
+
+    /// Access the planet's physics component.
+    pub fn phys(&self) -> &EntityPhysics {
+        self.entity.phys()
+    }
+
+    /// The radius of the planet's surface.
+    pub fn radius(&self) -> f32 {
+        self.collider().shape().bounding_radius()
+    }
+
+    /// Access the planet's collider.
+    pub fn collider(&self) -> &Collider {
+        self.entity.collider().expect("planets always have a collider")
+    }
+
+    /// Set which collision layers the planet is on and which it touches.
+    pub fn set_filter(&mut self, filter: CollisionFilter) {
+        self.entity.collider_mut().expect("planets always have a collider").set_filter(filter);
+    }
+}
+
+impl SimState for Planet {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        self.phys().hash_state(hasher);
+    }
+}
+
+impl SimState for PlanetManager {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_f64(self.time());
+        self.planets().hash_state(hasher);
+    }
+}
@@ -0,0 +1,332 @@
+//! Two-body orbit calculations.
+//!
+//! These treat an entity and a planet as an isolated pair, ignoring every other
+//! planet, and use the gravitational constant of the ``PlanetManager``.
+//! They are exact for one planet and a good approximation close to one planet.
+
+use std::f32::consts::PI;
+use sfml::system::Vector2f;
+use entity::EntityPhysics;
+use util::Vector2Ext;
+use super::PlanetManager;
+
+// Eccentricities closer than this to 1 are treated as parabolic.
+const PARABOLIC_TOLERANCE: f32 = 1e-6;
+
+// Newton iterations used to solve Kepler's equation.
+const KEPLER_ITERATIONS: usize = 12;
+
+/// The direction an orbit goes around its planet, as seen on screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrbitDirection {
+    /// The direction rotation increases in SFML, since y points down.
+    Clockwise,
+
+    /// The direction rotation decreases in SFML.
+    CounterClockwise,
+}
+
+impl OrbitDirection {
+    fn sign(&self) -> f32 {
+        match *self {
+            OrbitDirection::Clockwise => 1.,
+            OrbitDirection::CounterClockwise => -1.,
+        }
+    }
+}
+
+/// Describes the shape and orientation of an orbit, along with where
+/// the body is along it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitalElements {
+    mu: f32,
+    semi_latus_rectum: f32,
+    eccentricity: f32,
+    argument_of_periapsis: f32,
+    true_anomaly: f32,
+    direction: OrbitDirection,
+}
+
+impl OrbitalElements {
+    /// Compute the orbit of a body with position ``rel_pos`` and velocity ``rel_vel``
+    /// relative to a central body, given the gravitational parameter ``mu``.
+    /// See ``gravitational_parameter``. A body exactly at the center, or moving
+    /// straight towards or away from it, has no real orbit. It gets one with a
+    /// semi-latus rectum of zero, whose ``state`` is at rest at the center.
+    pub fn from_state(rel_pos: Vector2f, rel_vel: Vector2f, mu: f32) -> OrbitalElements {
+        let r = rel_pos.length();
+        if r == 0. {
+            return OrbitalElements {
+                mu,
+                semi_latus_rectum: 0.,
+                eccentricity: 0.,
+                argument_of_periapsis: 0.,
+                true_anomaly: 0.,
+                direction: OrbitDirection::Clockwise,
+            };
+        }
+
+        let h = rel_pos.cross(&rel_vel);
+
+        // Eccentricity vector: points at periapsis, with length e.
+        let e_vec = (rel_pos * (rel_vel.length_sq() - mu / r) - rel_vel * rel_pos.dot(&rel_vel)) / mu;
+        let eccentricity = e_vec.length();
+
+        let direction = if h >= 0. { OrbitDirection::Clockwise } else { OrbitDirection::CounterClockwise };
+
+        // A circle has no periapsis, so measure everything from the current position.
+        let (argument_of_periapsis, true_anomaly) = if eccentricity > PARABOLIC_TOLERANCE {
+            let arg = e_vec.y.atan2(e_vec.x);
+            let nu = e_vec.cross(&rel_pos).atan2(e_vec.dot(&rel_pos)) * direction.sign();
+            (arg.to_degrees(), nu.to_degrees())
+        } else {
+            (rel_pos.y.atan2(rel_pos.x).to_degrees(), 0.)
+        };
+
+        OrbitalElements {
+            mu,
+            semi_latus_rectum: h * h / mu,
+            eccentricity,
+            argument_of_periapsis,
+            true_anomaly,
+            direction,
+        }
+    }
+
+    /// A circular orbit of ``radius``, with the body at ``angle`` degrees
+    /// from the central body (``0`` is to the right).
+    pub fn circular(mu: f32, radius: f32, angle: f32, direction: OrbitDirection) -> OrbitalElements {
+        OrbitalElements {
+            mu,
+            semi_latus_rectum: radius,
+            eccentricity: 0.,
+            argument_of_periapsis: angle,
+            true_anomaly: 0.,
+            direction,
+        }
+    }
+
+    /// An elliptical orbit with the given closest and furthest distances.
+    /// ``argument_of_periapsis`` is the angle of the closest point in degrees,
+    /// and ``true_anomaly`` is the angle of the body past that point, in the
+    /// direction of motion.
+    pub fn elliptical(mu: f32, periapsis: f32, apoapsis: f32, argument_of_periapsis: f32,
+                      true_anomaly: f32, direction: OrbitDirection) -> OrbitalElements {
+        let (periapsis, apoapsis) = (periapsis.min(apoapsis), periapsis.max(apoapsis));
+        let eccentricity = (apoapsis - periapsis) / (apoapsis + periapsis);
+
+        OrbitalElements {
+            mu,
+            semi_latus_rectum: periapsis * (1. + eccentricity),
+            eccentricity,
+            argument_of_periapsis,
+            true_anomaly,
+            direction,
+        }
+    }
+
+    /// The same orbit with the body at a different true anomaly, in degrees.
+    pub fn with_true_anomaly(&self, true_anomaly: f32) -> OrbitalElements {
+        OrbitalElements {
+            true_anomaly,
+            ..*self
+        }
+    }
+
+    /// Position and velocity relative to the central body.
+    pub fn state(&self) -> (Vector2f, Vector2f) {
+        if self.is_degenerate() {
+            return (Vector2f::new(0., 0.), Vector2f::new(0., 0.));
+        }
+
+        let nu = self.true_anomaly.to_radians();
+        let (sin, cos) = nu.sin_cos();
+        let sign = self.direction.sign();
+
+        // Work with periapsis on the x axis, mirrored for counter-clockwise orbits,
+        // and then turn everything to face the real periapsis.
+        let r = self.semi_latus_rectum / (1. + self.eccentricity * cos);
+        let pos = Vector2f::new(r * cos, r * sin * sign);
+
+        let speed = (self.mu / self.semi_latus_rectum).sqrt();
+        let vel = Vector2f::new(-sin * speed, (self.eccentricity + cos) * speed * sign);
+
+        (pos.rotate(self.argument_of_periapsis), vel.rotate(self.argument_of_periapsis))
+    }
+
+    /// The gravitational parameter the orbit was computed with.
+    pub fn mu(&self) -> f32 {
+        self.mu
+    }
+
+    /// Eccentricity: ``0`` for a circle, below ``1`` for an ellipse,
+    /// ``1`` for a parabola and above ``1`` for a hyperbola.
+    pub fn eccentricity(&self) -> f32 {
+        self.eccentricity
+    }
+
+    /// The semi-latus rectum, which stays finite for every kind of orbit.
+    pub fn semi_latus_rectum(&self) -> f32 {
+        self.semi_latus_rectum
+    }
+
+    /// Half the longest diameter of the orbit. Negative for hyperbolic orbits,
+    /// and infinite for parabolic ones.
+    pub fn semi_major_axis(&self) -> f32 {
+        let denominator = 1. - self.eccentricity * self.eccentricity;
+        if denominator.abs() < PARABOLIC_TOLERANCE {
+            f32::INFINITY
+        } else {
+            self.semi_latus_rectum / denominator
+        }
+    }
+
+    /// Angle of the periapsis from the central body, in degrees.
+    pub fn argument_of_periapsis(&self) -> f32 {
+        self.argument_of_periapsis
+    }
+
+    /// Angle of the body past periapsis in the direction of motion, in degrees.
+    pub fn true_anomaly(&self) -> f32 {
+        self.true_anomaly
+    }
+
+    /// Which way the body goes around.
+    pub fn direction(&self) -> OrbitDirection {
+        self.direction
+    }
+
+    /// Whether the orbit has collapsed to a line or a point, which happens when the
+    /// body has no angular momentum. See ``from_state``.
+    pub fn is_degenerate(&self) -> bool {
+        self.semi_latus_rectum <= 0.
+    }
+
+    /// Whether the body will keep coming back, rather than escaping.
+    pub fn is_bound(&self) -> bool {
+        self.eccentricity < 1.
+    }
+
+    /// The closest distance to the central body.
+    pub fn periapsis(&self) -> f32 {
+        self.semi_latus_rectum / (1. + self.eccentricity)
+    }
+
+    /// The furthest distance from the central body, or ``None`` when unbound.
+    pub fn apoapsis(&self) -> Option<f32> {
+        if self.is_bound() {
+            Some(self.semi_latus_rectum / (1. - self.eccentricity))
+        } else {
+            None
+        }
+    }
+
+    /// The time for one full orbit in seconds, or ``None`` when unbound
+    /// or degenerate.
+    pub fn period(&self) -> Option<f32> {
+        if self.is_bound() && !self.is_degenerate() {
+            Some(2. * PI / self.mean_motion())
+        } else {
+            None
+        }
+    }
+
+    /// Orbital energy per unit mass. Negative for bound orbits.
+    pub fn specific_energy(&self) -> f32 {
+        -self.mu * (1. - self.eccentricity * self.eccentricity) / (2. * self.semi_latus_rectum)
+    }
+
+    /// Average angular speed around the orbit in radians per second.
+    /// Only meaningful for bound orbits, and zero for degenerate ones.
+    pub fn mean_motion(&self) -> f32 {
+        if self.is_degenerate() {
+            return 0.;
+        }
+
+        let a = self.semi_major_axis();
+        (self.mu / (a * a * a)).sqrt()
+    }
+
+    /// The mean anomaly in degrees, which unlike the true anomaly increases
+    /// at a constant rate. Only meaningful for bound orbits.
+    pub fn mean_anomaly(&self) -> f32 {
+        true_to_mean_anomaly(self.true_anomaly.to_radians(), self.eccentricity).to_degrees()
+    }
+
+    /// Where the body will be ``time`` seconds later, found by solving Kepler's equation.
+    /// Only meaningful for bound orbits.
+    pub fn after(&self, time: f32) -> OrbitalElements {
+        let mean = self.mean_anomaly().to_radians() + self.mean_motion() * time;
+        self.with_true_anomaly(mean_to_true_anomaly(mean, self.eccentricity).to_degrees())
+    }
+}
+
+/// The gravitational parameter of two bodies orbiting each other,
+/// given a gravitational constant such as ``GravityModel::constant``.
+/// If the central body can't move, as with an immovable planet, only its
+/// mass counts, so pass zero for ``orbiting_mass``.
+pub fn gravitational_parameter(constant: f32, central_mass: f32, orbiting_mass: f32) -> f32 {
+    constant * (central_mass + orbiting_mass)
+}
+
+impl PlanetManager {
+    /// The orbit of a physics component around a planet, by index.
+    /// Uses the manager's gravitational constant, ignoring softening and falloff.
+    pub fn orbit_of(&self, planet: usize, phys: &EntityPhysics) -> OrbitalElements {
+        let planet = self.planets[planet].phys();
+        OrbitalElements::from_state(phys.pos() - planet.pos(),
+                                    phys.vel() - planet.vel(),
+                                    self.mu(planet, phys.mass()))
+    }
+
+    /// World position and velocity for a body on ``orbit`` around a planet.
+    /// Use it to spawn ships and moons already in orbit.
+    pub fn orbit_state(&self, planet: usize, orbit: &OrbitalElements) -> (Vector2f, Vector2f) {
+        let planet = self.planets[planet].phys();
+        let (pos, vel) = orbit.state();
+        (planet.pos() + pos, planet.vel() + vel)
+    }
+
+    /// Put a physics component on ``orbit`` around a planet.
+    pub fn place_in_orbit(&self, planet: usize, phys: &mut EntityPhysics, orbit: &OrbitalElements) {
+        let (pos, vel) = self.orbit_state(planet, orbit);
+        phys.set_position(pos);
+        phys.set_vel(vel);
+    }
+
+    /// A circular orbit around a planet for a body of ``mass``.
+    pub fn circular_orbit(&self, planet: usize, mass: f32, radius: f32, angle: f32,
+                          direction: OrbitDirection) -> OrbitalElements {
+        let mu = self.mu(self.planets[planet].phys(), mass);
+        OrbitalElements::circular(mu, radius, angle, direction)
+    }
+
+    /// Whether ``phys`` is in a bound orbit around a planet that never touches its surface.
+    pub fn is_stable_orbit(&self, planet: usize, phys: &EntityPhysics) -> bool {
+        let orbit = self.orbit_of(planet, phys);
+        orbit.is_bound() && orbit.periapsis() > self.planets[planet].radius()
+    }
+
+    // Planets are usually immovable, so gravity only accelerates what orbits them.
+    fn mu(&self, planet: &EntityPhysics, orbiting_mass: f32) -> f32 {
+        let orbiting_mass = if planet.is_immovable() { 0. } else { orbiting_mass };
+        gravitational_parameter(self.gravity.constant, planet.mass(), orbiting_mass)
+    }
+}
+
+fn true_to_mean_anomaly(nu: f32, e: f32) -> f32 {
+    let eccentric = 2. * ((1. - e).sqrt() * (nu / 2.).sin()).atan2((1. + e).sqrt() * (nu / 2.).cos());
+    eccentric - e * eccentric.sin()
+}
+
+fn mean_to_true_anomaly(mean: f32, e: f32) -> f32 {
+    let mean = mean % (2. * PI);
+
+    // Newton's method on Kepler's equation, M = E - e sin E.
+    let mut eccentric = if e > 0.8 { PI } else { mean };
+    for _ in 0..KEPLER_ITERATIONS {
+        eccentric -= (eccentric - e * eccentric.sin() - mean) / (1. - e * eccentric.cos());
+    }
+
+    2. * ((1. + e).sqrt() * (eccentric / 2.).sin()).atan2((1. - e).sqrt() * (eccentric / 2.).cos())
+}
@@ -0,0 +1,93 @@
+//! Prescribed orbits for planets that should move predictably
+//! instead of under gravity.
+
+use std::f64::consts::PI;
+use sfml::system::Vector2f;
+use util::Vector2Ext;
+use super::orbit::OrbitalElements;
+
+/// What an orbit on rails is centered on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor {
+    /// A fixed point in world coordinates.
+    Point(Vector2f),
+
+    /// Another planet, by index in its ``PlanetManager``. The anchor planet
+    /// must be added to the manager before any planet that orbits it.
+    Planet(usize),
+}
+
+/// An orbit that a planet follows exactly, regardless of gravity.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rails {
+    anchor: Anchor,
+    path: Path,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Path {
+    Circular {
+        radius: f32,
+        period: f32,
+        phase: f32,
+    },
+    Elliptical(OrbitalElements),
+}
+
+impl Rails {
+    /// Create a circular orbit around ``anchor``.
+    /// ``period`` is the time for one revolution in seconds; a negative period
+    /// orbits the other way. ``phase`` is the starting angle in degrees,
+    /// where ``0`` is directly to the right of the anchor.
+    pub fn circular(anchor: Anchor, radius: f32, period: f32, phase: f32) -> Rails {
+        assert!(period != 0., "an orbit needs a non-zero period");
+
+        Rails {
+            anchor,
+            path: Path::Circular {
+                radius,
+                period,
+                phase,
+            },
+        }
+    }
+
+    /// Follow a Keplerian orbit around ``anchor``, starting from where ``orbit``
+    /// puts the body. The orbit must be bound and not degenerate. The speed along
+    /// the orbit comes from the orbit's gravitational parameter, not from the
+    /// anchor's real mass.
+    pub fn elliptical(anchor: Anchor, orbit: OrbitalElements) -> Rails {
+        assert!(orbit.is_bound(), "only bound orbits can be followed on rails");
+        assert!(!orbit.is_degenerate(), "a degenerate orbit can't be followed on rails");
+
+        Rails {
+            anchor,
+            path: Path::Elliptical(orbit),
+        }
+    }
+
+    /// Return what the orbit is centered on.
+    pub fn anchor(&self) -> Anchor {
+        self.anchor
+    }
+
+    /// Position and velocity relative to the anchor,
+    /// ``time`` seconds after the orbit started.
+    pub fn state_at(&self, time: f64) -> (Vector2f, Vector2f) {
+        match self.path {
+            Path::Circular { radius, period, phase } => {
+                // Reduce in f64 so long-running orbits don't lose precision.
+                let turns = (time / period as f64).fract();
+                let angle = phase + (turns * 360.) as f32;
+                let angular_vel = (2. * PI / period as f64) as f32;
+
+                let offset = Vector2f::from_angle(angle) * radius;
+                (offset, offset.perp() * angular_vel)
+            },
+            Path::Elliptical(ref orbit) => {
+                let period = orbit.period().unwrap() as f64;
+                orbit.after((time % period) as f32).state()
+            },
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Predicting where gravity will take an entity, and drawing the result.
+
+use sfml::graphics::{Color, Drawable, PrimitiveType, RenderStates, RenderTarget, Vertex,
+                     VertexArray};
+use sfml::system::Vector2f;
+use collision::{self, Collider};
+use entity::EntityPhysics;
+use super::PlanetManager;
+
+/// The predicted path of an entity.
+#[derive(Clone, Debug, Default)]
+pub struct Trajectory {
+    /// Sampled positions along the path, starting with the current position.
+    pub points: Vec<Vector2f>,
+
+    /// Where the path first runs into a planet, if it does.
+    pub impact: Option<Impact>,
+}
+
+/// Information about a predicted collision with a planet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Impact {
+    /// Index of the planet that is hit.
+    pub planet: usize,
+
+    /// The number of ticks until the impact.
+    pub tick: u32,
+
+    /// The time until the impact, in seconds.
+    pub time: f32,
+
+    /// The entity's position when it hits.
+    pub pos: Vector2f,
+
+    /// The entity's velocity when it hits.
+    pub vel: Vector2f,
+}
+
+impl PlanetManager {
+    /// Predict the path of a physics component for up to ``ticks`` ticks of
+    /// length ``dt``, recording every ``sample_every``th position.
+    /// A clone of ``phys`` is simulated, so nothing in the world changes.
+    ///
+    /// Atmospheric drag is included.
+    /// Planets are treated as staying where they are now, so predictions near
+    /// moving planets are only good for short horizons. The prediction stops at
+    /// the first planet the ``collider`` touches, or, without a collider, the first
+    /// planet the position enters.
+    pub fn predict(&self, phys: &EntityPhysics, collider: Option<&Collider>,
+                   ticks: u32, dt: f32, sample_every: u32) -> Trajectory {
+        let sample_every = sample_every.max(1);
+        let mut phys = phys.clone();
+        let mut trajectory = Trajectory {
+            points: vec![phys.pos()],
+            impact: None,
+        };
+
+        for tick in 1..=ticks {
+            self.apply_atmosphere(&mut phys, dt);
+            phys.update_in(dt, self);
+
+            if let Some(planet) = self.first_touching(&phys, collider) {
+                trajectory.points.push(phys.pos());
+                trajectory.impact = Some(Impact {
+                    planet,
+                    tick,
+                    time: tick as f32 * dt,
+                    pos: phys.pos(),
+                    vel: phys.vel(),
+                });
+                break;
+            }
+
+            if tick % sample_every == 0 {
+                trajectory.points.push(phys.pos());
+            }
+        }
+
+        trajectory
+    }
+
+    fn first_touching(&self, phys: &EntityPhysics, collider: Option<&Collider>) -> Option<usize> {
+        let pos = phys.pos();
+        let shape = collider.map(|c| (c.world_shape(phys), c.filter()));
+        let candidates = match shape {
+            Some((ref shape, _)) => self.index.query_region(&shape.bounds()),
+            None => self.index.query_point(pos),
+        };
+
+        candidates.into_iter()
+            .filter(|&i| {
+                let planet = &self.planets[i];
+                let planet_shape = planet.collider().world_shape(planet.phys());
+                match shape {
+                    Some((ref shape, filter)) => {
+                        filter.collides_with(&planet.collider().filter())
+                            && collision::collide(shape, &planet_shape).is_some()
+                    },
+                    None => planet_shape.contains(pos),
+                }
+            })
+            .min()
+    }
+}
+
+/// A dotted line showing a ``Trajectory``, fading out towards its end.
+#[derive(Debug)]
+pub struct TrajectoryLine {
+    vertices: VertexArray,
+    color: Color,
+}
+
+impl TrajectoryLine {
+    /// Create an empty line that will be drawn in ``color``.
+    pub fn new(color: Color) -> TrajectoryLine {
+        TrajectoryLine {
+            vertices: VertexArray::new(PrimitiveType::Lines, 0),
+            color,
+        }
+    }
+
+    /// Rebuild the line from a new prediction.
+    pub fn set_trajectory(&mut self, trajectory: &Trajectory) {
+        self.vertices.clear();
+
+        let points = &trajectory.points;
+        let count = points.len() as f32;
+
+        // Draw every other segment to get the dotted look.
+        for i in (1..points.len()).step_by(2) {
+            let mut color = self.color;
+            color.a = (color.a as f32 * (1. - i as f32 / count)) as u8;
+
+            self.vertices.append(&Vertex::with_pos_color(points[i - 1], color));
+            self.vertices.append(&Vertex::with_pos_color(points[i], color));
+        }
+    }
+
+    /// Return the color of the line.
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    /// Set the color of the line. Takes effect on the next ``set_trajectory``.
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+}
+
+impl Drawable for TrajectoryLine {
+    fn draw<'a: 'shader, 'texture, 'shader, 'shader_texture> (
+            &'a self,
+            target: &mut RenderTarget,
+            states: RenderStates<'texture, 'shader, 'shader_texture>) {
+        target.draw_with_renderstates(&self.vertices, states);
+    }
+}
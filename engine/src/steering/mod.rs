@@ -0,0 +1,271 @@
+//! Steering behaviours, for AI ships that fly with the same physics as the player.
+//!
+//! Each behaviour looks at an entity's ``EntityPhysics`` and returns the force
+//! that would move it towards the velocity it wants, no stronger than the
+//! entity's ``SteeringLimits``. Behaviours don't change anything themselves:
+//! mix their forces, by adding them up with weights or with ``prioritize``,
+//! then hand the result to ``steer`` to push the entity and turn it to face
+//! where it's going. Gravity still acts as usual, so a ship that stops
+//! steering falls like anything else.
+
+pub use self::wander::{Rng, Wander};
+
+pub mod wander;
+
+use sfml::system::Vector2f;
+use entity::EntityPhysics;
+use planet_manager::{OrbitDirection, PlanetManager};
+use util::{self, Vector2Ext};
+
+/// Seconds to reach a wanted velocity unless told otherwise.
+pub const DEFAULT_RESPONSE_TIME: f32 = 0.5;
+
+// The furthest ahead ``pursue`` and ``evade`` guess where their target will be, in seconds.
+const MAX_PREDICTION: f32 = 3.;
+
+// The shortest response time used, so a zero can't divide by zero.
+const MIN_RESPONSE_TIME: f32 = 1e-3;
+
+/// How hard and how fast a steered entity may go.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SteeringLimits {
+    /// The strongest steering force.
+    pub max_force: f32,
+
+    /// The fastest the entity will choose to fly. Gravity can still pull it faster.
+    pub max_speed: f32,
+
+    /// The strongest torque used to turn.
+    pub max_torque: f32,
+
+    /// Roughly how many seconds it takes to reach a wanted velocity or heading.
+    /// Shorter is twitchier. Must be positive; anything shorter than a
+    /// millisecond is treated as a millisecond.
+    pub response_time: f32,
+}
+
+impl SteeringLimits {
+    /// Create limits with the default response time.
+    pub fn new(max_force: f32, max_speed: f32, max_torque: f32) -> SteeringLimits {
+        SteeringLimits {
+            max_force,
+            max_speed,
+            max_torque,
+            response_time: DEFAULT_RESPONSE_TIME,
+        }
+    }
+
+    /// Create limits with a custom response time, which must be positive.
+    pub fn with_response_time(max_force: f32, max_speed: f32, max_torque: f32,
+                              response_time: f32) -> SteeringLimits {
+        assert!(response_time > 0., "response time must be positive");
+        SteeringLimits {
+            response_time,
+            ..SteeringLimits::new(max_force, max_speed, max_torque)
+        }
+    }
+
+    // The response time, kept away from zero.
+    fn response_time(&self) -> f32 {
+        self.response_time.max(MIN_RESPONSE_TIME)
+    }
+}
+
+/// Head for a point at full speed.
+pub fn seek<V: Into<Vector2f>>(phys: &EntityPhysics, limits: &SteeringLimits, target: V)
+                               -> Vector2f {
+    let direction = direction_or(target.into() - phys.pos(), phys.forward());
+    seek_velocity(phys, limits, direction * limits.max_speed)
+}
+
+/// Run directly away from a point at full speed.
+pub fn flee<V: Into<Vector2f>>(phys: &EntityPhysics, limits: &SteeringLimits, threat: V)
+                               -> Vector2f {
+    let direction = direction_or(phys.pos() - threat.into(), phys.forward());
+    seek_velocity(phys, limits, direction * limits.max_speed)
+}
+
+/// Head for a point, slowing down within ``slowing_radius`` to stop on it.
+pub fn arrive<V: Into<Vector2f>>(phys: &EntityPhysics, limits: &SteeringLimits, target: V,
+                                 slowing_radius: f32) -> Vector2f {
+    seek_velocity(phys, limits, arrive_velocity(phys, limits, target.into(), slowing_radius))
+}
+
+/// Head for where a moving target will be by the time we get there.
+pub fn pursue(phys: &EntityPhysics, limits: &SteeringLimits, quarry: &EntityPhysics) -> Vector2f {
+    seek(phys, limits, predict(phys, limits, quarry))
+}
+
+/// Run from where a moving threat will be by the time it gets here.
+pub fn evade(phys: &EntityPhysics, limits: &SteeringLimits, threat: &EntityPhysics) -> Vector2f {
+    flee(phys, limits, predict(phys, limits, threat))
+}
+
+/// Swerve around planets in the way. Looks ``look_ahead`` seconds ahead at
+/// the current velocity, and keeps ``clearance`` from each surface. The
+/// closer the planet, the harder the swerve, up to ``max_force``. Returns
+/// zero when nothing is in the way.
+pub fn avoid_planets(phys: &EntityPhysics, limits: &SteeringLimits, planets: &PlanetManager,
+                     look_ahead: f32, clearance: f32) -> Vector2f {
+    let speed = phys.speed();
+    let heading = if speed > 0. { phys.vel() / speed } else { phys.forward() };
+    let reach = speed * look_ahead;
+
+    // The nearest planet in the way, as (distance ahead, urgency, push direction).
+    let mut nearest: Option<(f32, f32, Vector2f)> = None;
+    for planet in planets.planets() {
+        let offset = planet.phys().pos() - phys.pos();
+        let safe = planet.radius() + clearance;
+        let ahead = offset.dot(&heading);
+        let lateral = offset - heading * ahead;
+
+        let threat = if offset.length_sq() < safe * safe {
+            // Already too close, so back straight off.
+            (0., 1., -offset)
+        } else if ahead > 0. && ahead < reach + safe && lateral.length_sq() < safe * safe {
+            // Turn towards whichever side of the path the planet's center isn't on.
+            let push = if lateral.length_sq() > 0. { -lateral } else { heading.perp() };
+            (ahead, 1. - ahead / (reach + safe), push)
+        } else {
+            continue;
+        };
+
+        if nearest.is_none_or(|(distance, _, _)| threat.0 < distance) {
+            nearest = Some(threat);
+        }
+    }
+
+    match nearest {
+        Some((_, urgency, push)) => direction_or(push, heading.perp()) * limits.max_force * urgency,
+        None => Vector2f::new(0., 0.),
+    }
+}
+
+/// Hold a slot in a formation, given in the leader's local frame, such as
+/// ``(40., 20.)`` for behind and to the right. Matches the leader's velocity
+/// and eases into the slot within ``slowing_radius``.
+pub fn keep_formation<V: Into<Vector2f>>(phys: &EntityPhysics, limits: &SteeringLimits,
+                                         leader: &EntityPhysics, slot: V, slowing_radius: f32)
+                                         -> Vector2f {
+    let target = leader.local_to_world(slot);
+    let desired = leader.vel() + arrive_velocity(phys, limits, target, slowing_radius);
+    seek_velocity(phys, limits, truncate(desired, limits.max_speed))
+}
+
+/// Settle into a circular orbit of ``radius`` around a planet, by index.
+/// Only corrects the orbit, since gravity does most of the work.
+/// Returns zero if there is no such planet.
+pub fn orbit(phys: &EntityPhysics, limits: &SteeringLimits, planets: &PlanetManager,
+             planet: usize, radius: f32, direction: OrbitDirection) -> Vector2f {
+    let center = match planets.planet(planet) {
+        Some(planet) => planet.phys().pos(),
+        None => return Vector2f::new(0., 0.),
+    };
+    let angle = (phys.pos() - center).angle();
+    let orbit = planets.circular_orbit(planet, phys.mass(), radius, angle, direction);
+
+    let (target_pos, target_vel) = planets.orbit_state(planet, &orbit);
+    let desired = target_vel + (target_pos - phys.pos()) / limits.response_time();
+    seek_velocity(phys, limits, truncate(desired, limits.max_speed))
+}
+
+/// Add up forces in order of importance until ``max_force`` is used up, so
+/// that, say, avoiding a planet wins over keeping formation. The force that
+/// doesn't fit is cut short, and everything after it is dropped.
+pub fn prioritize(forces: &[Vector2f], max_force: f32) -> Vector2f {
+    let mut total = Vector2f::new(0., 0.);
+    let mut left = max_force;
+
+    for &force in forces {
+        let length = force.length();
+        if length > left {
+            return total + force * (left / length);
+        }
+
+        total += force;
+        left -= length;
+    }
+
+    total
+}
+
+/// Apply a steering force, no stronger than ``max_force`` and never speeding
+/// the entity up past ``max_speed``, and turn it to face the direction of the
+/// force with at most ``max_torque``.
+pub fn steer(phys: &mut EntityPhysics, limits: &SteeringLimits, force: Vector2f) {
+    let mut force = truncate(force, limits.max_force);
+
+    let speed = phys.speed();
+    if speed > limits.max_speed {
+        let heading = phys.vel() / speed;
+        let speeding_up = force.dot(&heading);
+        if speeding_up > 0. {
+            force -= heading * speeding_up;
+        }
+    }
+
+    phys.apply_force(force);
+
+    if force == Vector2f::new(0., 0.) {
+        return;
+    }
+
+    // Forward is local -y, a quarter turn back from the angle of local +x. Turn
+    // like a critically damped spring, so the entity swings round without wobbling.
+    let heading = util::normalize_angle(force.angle() + 90.);
+    let error = util::angle_delta(phys.rot(), heading).to_radians();
+    let frequency = 2. / limits.response_time();
+    let torque = (error * frequency * frequency - phys.angular_vel_radians() * 2. * frequency)
+        * phys.rotational_inertia();
+    phys.apply_torque(torque.clamp(-limits.max_torque, limits.max_torque));
+}
+
+// The force to reach ``desired`` velocity over the response time.
+fn seek_velocity(phys: &EntityPhysics, limits: &SteeringLimits, desired: Vector2f) -> Vector2f {
+    truncate((desired - phys.vel()) * phys.mass() / limits.response_time(), limits.max_force)
+}
+
+// The velocity that heads for ``target`` and stops on it.
+fn arrive_velocity(phys: &EntityPhysics, limits: &SteeringLimits, target: Vector2f,
+                   slowing_radius: f32) -> Vector2f {
+    let offset = target - phys.pos();
+    let distance = offset.length();
+    if distance == 0. {
+        return Vector2f::new(0., 0.);
+    }
+
+    let speed = if distance < slowing_radius {
+        limits.max_speed * distance / slowing_radius
+    } else {
+        limits.max_speed
+    };
+    offset * (speed / distance)
+}
+
+// Where ``other`` will be when an entity at full speed could reach it.
+fn predict(phys: &EntityPhysics, limits: &SteeringLimits, other: &EntityPhysics) -> Vector2f {
+    let distance = (other.pos() - phys.pos()).length();
+    let time = if limits.max_speed > 0. {
+        (distance / limits.max_speed).min(MAX_PREDICTION)
+    } else {
+        0.
+    };
+    other.pos() + other.vel() * time
+}
+
+fn truncate(v: Vector2f, max: f32) -> Vector2f {
+    let length = v.length();
+    if length > max {
+        v * (max / length)
+    } else {
+        v
+    }
+}
+
+fn direction_or(v: Vector2f, fallback: Vector2f) -> Vector2f {
+    if v == Vector2f::new(0., 0.) {
+        fallback
+    } else {
+        v.normalize()
+    }
+}
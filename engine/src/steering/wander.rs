@@ -0,0 +1,99 @@
+//! Aimless but smooth wandering, driven by a seeded random number generator
+//! so that replays wander the same way.
+
+use std::f32::consts::PI;
+use sfml::system::Vector2f;
+use entity::EntityPhysics;
+use util::Vector2Ext;
+use super::{direction_or, seek_velocity, SteeringLimits};
+
+/// A small, fast pseudo-random number generator (xorshift64*).
+/// The same seed always gives the same numbers, on every platform.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Create a generator from a seed. Any seed works, including zero.
+    pub fn new(seed: u64) -> Rng {
+        // Xorshift gets stuck at zero, so mix the seed into a nonzero state.
+        Rng {
+            state: seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1,
+        }
+    }
+
+    /// The next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A random float in ``[0, 1)``.
+    pub fn next_f32(&mut self) -> f32 {
+        // The top 24 bits fill an f32 mantissa exactly.
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// A random float in ``[min, max)``.
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
+/// Wandering state for one entity. Each tick a target on a circle ahead of
+/// the entity drifts a little at random, and the entity steers towards it,
+/// giving gentle curves rather than jitter.
+#[derive(Clone, Debug)]
+pub struct Wander {
+    rng: Rng,
+    radius: f32,
+    distance: f32,
+    jitter: f32,
+    angle: f32,
+}
+
+impl Wander {
+    /// Create a wanderer. The target circle has ``radius`` and sits ``distance``
+    /// ahead; ``jitter`` is the most the target can move around it per second,
+    /// in radians.
+    pub fn new(seed: u64, radius: f32, distance: f32, jitter: f32) -> Wander {
+        let mut rng = Rng::new(seed);
+        let angle = rng.range(-PI, PI);
+
+        Wander {
+            rng,
+            radius,
+            distance,
+            jitter,
+            angle,
+        }
+    }
+
+    /// The steering force for this tick of ``dt`` seconds.
+    pub fn force(&mut self, phys: &EntityPhysics, limits: &SteeringLimits, dt: f32) -> Vector2f {
+        self.angle += self.rng.range(-1., 1.) * self.jitter * dt;
+
+        // Wander relative to where the entity is going, or where it faces if it's still.
+        // The angle is measured from the heading, so zero is straight ahead.
+        let heading = if phys.speed() > 0. { phys.vel().normalize() } else { phys.forward() };
+        let (sin, cos) = self.angle.sin_cos();
+        let offset = heading * (self.distance + cos * self.radius)
+            + heading.perp() * (sin * self.radius);
+
+        // The target sits on the entity itself if the circle reaches back that far.
+        seek_velocity(phys, limits, direction_or(offset, heading) * limits.max_speed)
+    }
+
+    /// Access the random number generator, to save it with a replay.
+    pub fn rng(&self) -> &Rng {
+        &self.rng
+    }
+
+    /// Replace the random number generator, to restore it from a replay.
+    pub fn set_rng(&mut self, rng: Rng) {
+        self.rng = rng;
+    }
+}
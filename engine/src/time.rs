@@ -0,0 +1,244 @@
+//! Fixed-timestep timing for the game loop.
+//!
+//! The simulation advances in ticks of a fixed length (``1 / TICKS_SEC`` seconds)
+//! no matter how fast frames are rendered. A ``GameClock`` accumulates elapsed
+//! time and reports how many ticks should be run each frame, along with an
+//! interpolation factor for rendering between the last two ticks.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+use entity::TICKS_SEC;
+
+/// The default maximum number of ticks run in a single frame.
+pub const DEFAULT_MAX_TICKS_FRAME: u32 = 8;
+
+/// A monotonic source of time for a ``GameClock``.
+pub trait ClockSource {
+    /// Time elapsed since some fixed starting point.
+    /// Must never decrease between calls.
+    fn elapsed(&self) -> Duration;
+}
+
+impl<C: ClockSource> ClockSource for &C {
+    fn elapsed(&self) -> Duration {
+        (**self).elapsed()
+    }
+}
+
+/// Real time, measured from when the clock was created.
+#[derive(Debug)]
+pub struct SystemClock {
+    begin: Instant,
+}
+
+impl SystemClock {
+    /// Create a new ``SystemClock`` starting now.
+    pub fn new() -> SystemClock {
+        SystemClock {
+            begin: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> SystemClock {
+        Self::new()
+    }
+}
+
+impl ClockSource for SystemClock {
+    fn elapsed(&self) -> Duration {
+        Instant::now().duration_since(self.begin)
+    }
+}
+
+/// A clock that only moves when told to.
+/// Pass ``&ManualClock`` to ``GameClock::with_source`` to drive ticks by hand,
+/// which is useful for tests and replays.
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: Cell<Duration>,
+}
+
+impl ManualClock {
+    /// Create a new ``ManualClock`` at time zero.
+    pub fn new() -> ManualClock {
+        Default::default()
+    }
+
+    /// Move the clock forward.
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+
+    /// Move the clock forward by a number of seconds.
+    pub fn advance_secs(&self, secs: f64) {
+        self.advance(duration_from_secs(secs));
+    }
+}
+
+impl ClockSource for ManualClock {
+    fn elapsed(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+/// Fixed-timestep accumulator that runs at ``TICKS_SEC`` ticks per second.
+#[derive(Debug)]
+pub struct GameClock<C: ClockSource = SystemClock> {
+    source: C,
+    tick_len: Duration,
+    last: Duration,
+    accumulator: Duration,
+    max_ticks_frame: u32,
+    total_ticks: u64,
+}
+
+impl GameClock<SystemClock> {
+    /// Create a new ``GameClock`` using real time.
+    pub fn new() -> GameClock<SystemClock> {
+        Self::with_source(SystemClock::new())
+    }
+}
+
+impl Default for GameClock<SystemClock> {
+    fn default() -> GameClock<SystemClock> {
+        Self::new()
+    }
+}
+
+impl<C: ClockSource> GameClock<C> {
+    /// Create a new ``GameClock`` driven by a custom ``ClockSource``.
+    pub fn with_source(source: C) -> GameClock<C> {
+        let last = source.elapsed();
+
+        GameClock {
+            source,
+            tick_len: duration_from_secs(1. / TICKS_SEC as f64),
+            last,
+            accumulator: Duration::new(0, 0),
+            max_ticks_frame: DEFAULT_MAX_TICKS_FRAME,
+            total_ticks: 0,
+        }
+    }
+
+    /// Set the maximum number of ticks that ``advance`` will ever return.
+    /// If the game falls further behind than this, the extra time is dropped
+    /// instead of being caught up, which keeps one slow frame from causing
+    /// an ever-growing backlog of ticks.
+    pub fn set_max_ticks_frame(&mut self, max_ticks_frame: u32) {
+        self.max_ticks_frame = max_ticks_frame.max(1);
+    }
+
+    /// Return the maximum number of ticks run per frame.
+    pub fn max_ticks_frame(&self) -> u32 {
+        self.max_ticks_frame
+    }
+
+    /// Read the clock source and return the number of ticks that should be
+    /// simulated this frame. Call this once per frame.
+    pub fn advance(&mut self) -> u32 {
+        let now = self.source.elapsed();
+        self.accumulator += now - self.last;
+        self.last = now;
+
+        let tick_nanos = self.tick_len.as_nanos();
+        let pending = self.accumulator.as_nanos() / tick_nanos;
+        let ticks = pending.min(self.max_ticks_frame as u128) as u32;
+
+        if pending > ticks as u128 {
+            // Spiral-of-death guard: forget the time we can't catch up on,
+            // but keep the fraction of a tick so ``alpha`` stays continuous.
+            let remainder = self.accumulator.as_nanos() % tick_nanos;
+            self.accumulator = Duration::from_nanos(remainder as u64);
+        } else {
+            self.accumulator -= self.tick_len * ticks;
+        }
+
+        self.total_ticks += ticks as u64;
+        ticks
+    }
+
+    /// The fraction of a tick that has elapsed since the last simulated tick,
+    /// in the range ``[0, 1)``. Use it to blend between the previous and current
+    /// physics state when rendering.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator.as_nanos() as f64 / self.tick_len.as_nanos() as f64) as f32
+    }
+
+    /// The length of a tick in seconds.
+    pub fn dt(&self) -> f32 {
+        1. / TICKS_SEC as f32
+    }
+
+    /// The total number of ticks simulated so far.
+    pub fn total_ticks(&self) -> u64 {
+        self.total_ticks
+    }
+
+    /// Access the clock source.
+    pub fn source(&self) -> &C {
+        &self.source
+    }
+}
+
+fn duration_from_secs(secs: f64) -> Duration {
+    Duration::from_nanos((secs * 1e9) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameClock, ManualClock, DEFAULT_MAX_TICKS_FRAME};
+
+    #[test]
+    fn runs_whole_ticks_and_keeps_the_rest() {
+        let manual = ManualClock::new();
+        let mut clock = GameClock::with_source(&manual);
+        let tick_len = clock.tick_len;
+
+        assert_eq!(clock.advance(), 0);
+        assert_eq!(clock.alpha(), 0.);
+
+        manual.advance(tick_len / 2);
+        assert_eq!(clock.advance(), 0);
+        assert!((clock.alpha() - 0.5).abs() < 1e-6);
+
+        manual.advance(tick_len * 2);
+        assert_eq!(clock.advance(), 2);
+        assert!((clock.alpha() - 0.5).abs() < 1e-6);
+
+        manual.advance(tick_len / 2);
+        assert_eq!(clock.advance(), 1);
+        assert_eq!(clock.alpha(), 0.);
+        assert_eq!(clock.total_ticks(), 3);
+    }
+
+    #[test]
+    fn drops_time_it_cant_catch_up_on() {
+        let manual = ManualClock::new();
+        let mut clock = GameClock::with_source(&manual);
+        let tick_len = clock.tick_len;
+
+        manual.advance(tick_len * 100 + tick_len / 4);
+        assert_eq!(clock.advance(), DEFAULT_MAX_TICKS_FRAME);
+        assert!((clock.alpha() - 0.25).abs() < 1e-6);
+
+        clock.set_max_ticks_frame(0);
+        assert_eq!(clock.max_ticks_frame(), 1);
+        manual.advance(tick_len * 3);
+        assert_eq!(clock.advance(), 1);
+        assert!((clock.alpha() - 0.25).abs() < 1e-6);
+        assert_eq!(clock.total_ticks(), DEFAULT_MAX_TICKS_FRAME as u64 + 1);
+    }
+
+    #[test]
+    fn starts_from_the_source_time() {
+        let manual = ManualClock::new();
+        manual.advance_secs(10.);
+        let mut clock = GameClock::with_source(&manual);
+
+        assert_eq!(clock.advance(), 0);
+        manual.advance_secs(clock.dt() as f64 * 1.5);
+        assert_eq!(clock.advance(), 1);
+    }
+}
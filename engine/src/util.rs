@@ -1,5 +1,5 @@
 //! This module provides additional math for the SFML ``Vector2<T>``
-//! by providing an extention trait.
+//! by providing an extention trait, as well as some angle helpers.
 
 use sfml::system::Vector2;
 use num_traits::float::Float;
@@ -14,6 +14,28 @@ pub trait Vector2Ext<T> {
 
     /// Normalize the vector.
     fn normalize(&self) -> Self;
+
+    /// Dot product.
+    fn dot(&self, other: &Self) -> T;
+
+    /// The z component of the 3D cross product, treating both vectors as lying
+    /// in the xy plane.
+    fn cross(&self, other: &Self) -> T;
+
+    /// The vector turned a quarter turn, ``(-y, x)``. This is the direction
+    /// a point at this offset moves when rotating in the positive direction.
+    fn perp(&self) -> Self;
+
+    /// Rotate the vector by an angle in degrees, in the same direction
+    /// as SFML's ``Transformable::rotate``.
+    fn rotate(&self, degrees: T) -> Self;
+
+    /// The angle of the vector in degrees, in ``[0, 360)``, measured the same
+    /// way as ``rotate``: ``0`` points along x and ``90`` along y.
+    fn angle(&self) -> T;
+
+    /// A unit vector at an angle in degrees, measured like ``angle``.
+    fn from_angle(degrees: T) -> Self;
 }
 
 impl<T: Float> Vector2Ext<T> for Vector2<T> {
@@ -28,4 +50,57 @@ impl<T: Float> Vector2Ext<T> for Vector2<T> {
     fn normalize(&self) -> Self {
         *self / self.length()
     }
+
+    fn dot(&self, other: &Self) -> T {
+        self.x * other.x + self.y * other.y
+    }
+
+    fn cross(&self, other: &Self) -> T {
+        self.x * other.y - self.y * other.x
+    }
+
+    fn perp(&self) -> Self {
+        Vector2::new(-self.y, self.x)
+    }
+
+    fn rotate(&self, degrees: T) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Vector2::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+    }
+
+    fn angle(&self) -> T {
+        let full = T::from(360.).unwrap();
+        let degrees = self.y.atan2(self.x).to_degrees();
+        let wrapped = if degrees < T::zero() { degrees + full } else { degrees };
+
+        // Tiny negative angles can round up to exactly 360.
+        if wrapped >= full { T::zero() } else { wrapped }
+    }
+
+    fn from_angle(degrees: T) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Vector2::new(cos, sin)
+    }
+}
+
+/// Wrap an angle in degrees into the canonical range ``[0, 360)``,
+/// the same range SFML reports rotations in.
+pub fn normalize_angle(degrees: f32) -> f32 {
+    let wrapped = degrees.rem_euclid(360.);
+
+    // Tiny negative angles can round up to exactly 360.
+    if wrapped >= 360. { 0. } else { wrapped }
+}
+
+/// Return the signed difference ``to - from`` between two angles in degrees,
+/// taking the shortest way around the circle. The result is in ``[-180, 180)``.
+pub fn angle_delta(from: f32, to: f32) -> f32 {
+    let delta = (to - from) % 360.;
+    if delta >= 180. {
+        delta - 360.
+    } else if delta < -180. {
+        delta + 360.
+    } else {
+        delta
+    }
 }
@@ -2,6 +2,7 @@
 
 use std::ops::{Deref, DerefMut};
 use sfml::graphics::{RenderTarget, RenderWindow, View};
+use sfml::system::Vector2f;
 use sfml::window::VideoMode;
 use entity::Entity;
 
@@ -33,7 +34,7 @@ impl GameWindow {
     /// Note that this method will center the entity in relation
     /// to the position determined by the physics component.
     /// This position is not necessarilly the entity's center.
-    pub fn center_view_on<E: Entity>(&mut self, entity: &E) {
+    pub fn center_view_on<E: Entity + ?Sized>(&mut self, entity: &E) {
         let mut view = self.inner.view().to_owned();
 
         let phys = entity.phys();
@@ -42,6 +43,21 @@ impl GameWindow {
         self.inner.set_view(&view);
     }
 
+    /// Like ``center_view_on``, but centers on the entity's position
+    /// interpolated between ticks. Use the same ``alpha`` the entity
+    /// is drawn with to keep the camera from jittering.
+    pub fn center_view_on_interpolated<E: Entity + ?Sized>(&mut self, entity: &E, alpha: f32) {
+        let pos = entity.phys().interpolated_pos(alpha);
+        self.center_view_at(pos);
+    }
+
+    /// Center the window's view on a point in world coordinates.
+    pub fn center_view_at<V: Into<Vector2f>>(&mut self, pos: V) {
+        let mut view = self.inner.view().to_owned();
+        view.set_center(pos.into());
+        self.inner.set_view(&view);
+    }
+
     /// Reset the window's view to the view provided by SFML's
     /// ``default_view()``.
     pub fn reset_view(&mut self) {
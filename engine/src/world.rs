@@ -0,0 +1,395 @@
+//! A registry that owns the game's entities and runs them each tick.
+//!
+//! A ``World`` stores boxed entities behind ``Handle``s. A handle names one
+//! entity for as long as it lives: once the entity is despawned its slot may be
+//! reused, but with a new generation, so stale handles find nothing rather than
+//! the newcomer.
+//!
+//! While the world is being iterated, entities can't be added or removed
+//! directly. Queue ``Commands`` instead; they run in order once the pass is
+//! over. Spawning through ``Commands`` hands back the new entity's handle
+//! straight away, though it only starts being found once the commands run.
+
+use std::any::Any;
+use std::hash::Hasher;
+use sfml::graphics::{Drawable, RenderStates, RenderTarget};
+use checksum::{SimState, StateHasher};
+use collision;
+use constraint::ConstraintSolver;
+use entity::{Entity, EntityPhysics, ForceField};
+use planet_manager::PlanetManager;
+
+/// Anything the world can own: an ``Entity`` that can also be drawn.
+/// Implemented for every such type.
+pub trait WorldEntity: Entity + Drawable {
+    /// Access the entity as ``Any``, to get back its concrete type.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Mutably access the entity as ``Any``, to get back its concrete type.
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Entity + Drawable + 'static> WorldEntity for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Names an entity in a ``World``, or in an ``ecs::Registry``.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Handle {
+    index: u32,
+    generation: u32,
+}
+
+impl Handle {
+    /// Return the slot the entity lives in.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Return how many times the slot had been reused when the entity was spawned.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+/// Entity changes queued while the world is busy. See the module documentation.
+pub struct Commands<'a> {
+    allocator: &'a mut Allocator,
+    queue: &'a mut Vec<Command>,
+}
+
+impl<'a> Commands<'a> {
+    /// Queue an entity to be added, returning the handle it will have.
+    pub fn spawn<E: WorldEntity + 'static>(&mut self, entity: E) -> Handle {
+        self.spawn_boxed(Box::new(entity))
+    }
+
+    /// Queue an already boxed entity to be added, returning the handle it will have.
+    pub fn spawn_boxed(&mut self, entity: Box<dyn WorldEntity>) -> Handle {
+        let handle = self.allocator.allocate();
+        self.queue.push(Command::Spawn(handle, entity));
+        handle
+    }
+
+    /// Queue an entity to be removed. Removing one that is already gone does nothing.
+    pub fn despawn(&mut self, handle: Handle) {
+        self.queue.push(Command::Despawn(handle));
+    }
+
+    /// Return the number of queued commands.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Return whether nothing is queued.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+/// Owns entities behind generational handles. See the module documentation.
+/// Passes visit entities in order of their slot, so stepping is deterministic.
+pub struct World {
+    entities: Vec<Option<Box<dyn WorldEntity>>>,
+    allocator: Allocator,
+    queue: Vec<Command>,
+    len: usize,
+}
+
+impl World {
+    /// Create an empty world.
+    pub fn new() -> World {
+        World {
+            entities: Vec::new(),
+            allocator: Allocator::default(),
+            queue: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Add an entity now, returning its handle.
+    pub fn spawn<E: WorldEntity + 'static>(&mut self, entity: E) -> Handle {
+        self.spawn_boxed(Box::new(entity))
+    }
+
+    /// Add an already boxed entity now, returning its handle.
+    pub fn spawn_boxed(&mut self, entity: Box<dyn WorldEntity>) -> Handle {
+        let handle = self.allocator.allocate();
+        self.insert(handle, entity);
+        handle
+    }
+
+    /// Remove an entity now, returning it if it was there.
+    pub fn despawn(&mut self, handle: Handle) -> Option<Box<dyn WorldEntity>> {
+        if !self.contains(handle) {
+            return None;
+        }
+
+        self.allocator.release(handle);
+        self.len -= 1;
+        self.entities[handle.index as usize].take()
+    }
+
+    /// Test whether a handle names a live entity.
+    pub fn contains(&self, handle: Handle) -> bool {
+        self.allocator.is_current(handle)
+            && self.entities.get(handle.index as usize).is_some_and(Option::is_some)
+    }
+
+    /// Access an entity.
+    pub fn get(&self, handle: Handle) -> Option<&dyn WorldEntity> {
+        if !self.contains(handle) {
+            return None;
+        }
+
+        self.entities[handle.index as usize].as_deref()
+    }
+
+    /// Mutably access an entity.
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut dyn WorldEntity> {
+        if !self.contains(handle) {
+            return None;
+        }
+
+        match self.entities[handle.index as usize] {
+            Some(ref mut entity) => Some(&mut **entity),
+            None => None,
+        }
+    }
+
+    /// Access an entity as its concrete type. ``None`` if it isn't a ``T``.
+    pub fn get_as<T: 'static>(&self, handle: Handle) -> Option<&T> {
+        self.get(handle)?.as_any().downcast_ref()
+    }
+
+    /// Mutably access an entity as its concrete type. ``None`` if it isn't a ``T``.
+    pub fn get_as_mut<T: 'static>(&mut self, handle: Handle) -> Option<&mut T> {
+        self.get_mut(handle)?.as_any_mut().downcast_mut()
+    }
+
+    /// Return the number of live entities.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return whether there are no live entities.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over every live entity along with its handle.
+    pub fn iter(&self) -> impl Iterator<Item = (Handle, &dyn WorldEntity)> {
+        let allocator = &self.allocator;
+        self.entities.iter()
+            .enumerate()
+            .filter_map(move |(index, entity)| {
+                entity.as_ref().map(|entity| (allocator.handle(index), &**entity))
+            })
+    }
+
+    /// Queue changes to make after the current pass. Run them with ``apply_commands``.
+    pub fn commands(&mut self) -> Commands<'_> {
+        Commands {
+            allocator: &mut self.allocator,
+            queue: &mut self.queue,
+        }
+    }
+
+    /// Run queued commands in the order they were queued.
+    pub fn apply_commands(&mut self) {
+        for command in ::std::mem::take(&mut self.queue) {
+            match command {
+                Command::Spawn(handle, entity) => self.insert(handle, entity),
+                Command::Despawn(handle) => {
+                    self.despawn(handle);
+                },
+            }
+        }
+    }
+
+    /// Call ``f`` on every live entity, with a queue for spawning and
+    /// despawning along the way. Queued commands run at the end.
+    pub fn for_each_mut<F>(&mut self, mut f: F)
+        where F: FnMut(Handle, &mut dyn WorldEntity, &mut Commands)
+    {
+        {
+            let mut commands = Commands {
+                allocator: &mut self.allocator,
+                queue: &mut self.queue,
+            };
+
+            for (index, entity) in self.entities.iter_mut().enumerate() {
+                if let Some(ref mut entity) = *entity {
+                    let handle = commands.allocator.handle(index);
+                    f(handle, &mut **entity, &mut commands);
+                }
+            }
+        }
+
+        self.apply_commands();
+    }
+
+    /// Apply the gravity of planets to every entity.
+    /// See ``PlanetManager::affect_entity``.
+    pub fn apply_gravity(&mut self, planets: &PlanetManager) {
+        self.for_each_mut(|_, entity, _| planets.affect_entity(entity));
+    }
+
+    /// Advance every entity by ``dt`` seconds under the influence of
+    /// ``field``, then run queued commands.
+    pub fn update(&mut self, dt: f32, field: &dyn ForceField) {
+        self.for_each_mut(|_, entity, _| entity.update(dt, field));
+    }
+
+    /// Bounce entities with colliders off each other, after they have been updated.
+    /// See ``collision::collide_all``.
+    pub fn collide(&mut self) {
+        let mut entities: Vec<&mut dyn WorldEntity> = self.entities.iter_mut()
+            .flatten()
+            .map(|entity| &mut **entity)
+            .collect();
+        collision::collide_all(&mut entities);
+    }
+
+    /// Enforce the joints of ``solver`` over a tick of ``dt`` seconds, after
+    /// entities have been updated and collided. Joints refer to entities by their
+    /// index in ``bodies``. Returns the ids of joints that broke, as
+    /// ``ConstraintSolver::solve`` does, and fails if an entity in ``bodies``
+    /// is gone or listed twice.
+    pub fn solve_constraints(&mut self, solver: &mut ConstraintSolver, bodies: &[Handle], dt: f32)
+                             -> Result<Vec<usize>, String> {
+        let mut slots: Vec<Option<&mut EntityPhysics>> = self.entities.iter_mut()
+            .map(|entity| entity.as_mut().map(|entity| entity.phys_mut()))
+            .collect();
+
+        let mut phys = Vec::with_capacity(bodies.len());
+        for (i, &handle) in bodies.iter().enumerate() {
+            let slot = if self.allocator.is_current(handle) {
+                slots.get_mut(handle.index as usize).and_then(Option::take)
+            } else {
+                None
+            };
+
+            match slot {
+                Some(slot) => phys.push(slot),
+                None => return Err(format!("body {}: no such entity, or listed twice", i)),
+            }
+        }
+
+        solver.solve(&mut phys, dt)
+    }
+
+    /// Blend every entity between the previous and current tick for drawing.
+    pub fn interpolate(&mut self, alpha: f32) {
+        for entity in self.entities.iter_mut().flatten() {
+            entity.interpolate(alpha);
+        }
+    }
+
+    fn insert(&mut self, handle: Handle, entity: Box<dyn WorldEntity>) {
+        let index = handle.index as usize;
+        if index >= self.entities.len() {
+            self.entities.resize_with(index + 1, || None);
+        }
+
+        self.entities[index] = Some(entity);
+        self.len += 1;
+    }
+}
+
+impl Default for World {
+    fn default() -> World {
+        World::new()
+    }
+}
+
+impl Drawable for World {
+    fn draw<'a: 'shader, 'texture, 'shader, 'shader_texture> (
+            &'a self,
+            target: &mut dyn RenderTarget,
+            states: RenderStates<'texture, 'shader, 'shader_texture>) {
+        for entity in self.entities.iter().flatten() {
+            let states = RenderStates {
+                blend_mode: states.blend_mode,
+                transform: states.transform,
+                texture: states.texture,
+                shader: states.shader,
+            };
+
+            entity.draw(target, states);
+        }
+    }
+}
+
+enum Command {
+    Spawn(Handle, Box<dyn WorldEntity>),
+    Despawn(Handle),
+}
+
+// Hands out handles, bumping a slot's generation each time it is freed.
+#[derive(Debug, Default)]
+pub(crate) struct Allocator {
+    generations: Vec<u32>,
+    free: Vec<u32>,
+}
+
+impl Allocator {
+    pub(crate) fn allocate(&mut self) -> Handle {
+        match self.free.pop() {
+            Some(index) => Handle {
+                index,
+                generation: self.generations[index as usize],
+            },
+            None => {
+                self.generations.push(0);
+                Handle {
+                    index: self.generations.len() as u32 - 1,
+                    generation: 0,
+                }
+            },
+        }
+    }
+
+    pub(crate) fn release(&mut self, handle: Handle) {
+        let generation = &mut self.generations[handle.index as usize];
+        *generation = generation.wrapping_add(1);
+        self.free.push(handle.index);
+    }
+
+    pub(crate) fn is_current(&self, handle: Handle) -> bool {
+        self.generations.get(handle.index as usize) == Some(&handle.generation)
+    }
+
+    // The current handle for a slot.
+    pub(crate) fn handle(&self, index: usize) -> Handle {
+        Handle {
+            index: index as u32,
+            generation: self.generations[index],
+        }
+    }
+}
+
+impl SimState for Handle {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u32(self.index);
+        hasher.write_u32(self.generation);
+    }
+}
+
+impl SimState for World {
+    /// Hashes each entity's handle and physics. Anything else an entity keeps,
+    /// such as thrusters, has to be hashed separately.
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.len());
+        for (handle, entity) in self.iter() {
+            handle.hash_state(hasher);
+            entity.phys().hash_state(hasher);
+        }
+    }
+}
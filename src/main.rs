@@ -1,21 +1,54 @@
 extern crate sfml;
 extern crate engine;
 
-use std::time::Instant;
 use sfml::graphics::{BlendMode, Color, RenderStates, RenderTarget};
 use sfml::graphics::blend_mode::Equation;
+use sfml::system::Vector2f;
 use sfml::window::{mouse, Event};
 use engine::background::{BackdropKind, BackgroundBuilder};
-use engine::entity::{TICKS_SEC, Entity, EntityPhysics, SpriteEntity};
+use engine::behaviour::{Agent, Arg, BehaviourTree, Blackboard, Status};
+use engine::collision::{Collider, Shape};
+use engine::constraint::{ConstraintSolver, Joint, JointKind};
+use engine::ecs::{systems, Registry, Schedule};
+use engine::entity::{Entity, EntityPhysics, Integrator, SpriteEntity, Thruster, Thrusters};
 use engine::input::Inputs;
-use engine::planet_manager::{Planet, PlanetManager};
-//use engine::refcounted::RcSprite;
+use engine::planet_manager::{Atmosphere, GravityModel, OrbitDirection, Planet, PlanetManager,
+                             SoiEvent, SoiTracker, TrajectoryLine};
+use engine::refcounted::RcSprite;
 use engine::resources::{ResourceId, Resources, TexOptions};
 //use engine::starfield;
+use engine::steering::{self, SteeringLimits};
+use engine::time::GameClock;
+use engine::util::Vector2Ext;
 use engine::window::GameWindow;
+use engine::world::World;
 
 const SIZE: (u32, u32) = (800, 600);
 const BG_ALPHA: u8 = 128;
+const THRUST: f32 = 100.;
+const TORQUE: f32 = 0.35;
+const FUEL: f32 = 1000.;
+const ENGINE_FUEL_RATE: f32 = 10.;
+const RCS_FUEL_RATE: f32 = 1.;
+const PREDICTION_TICKS: u32 = 270;
+const PREDICTION_SAMPLE: u32 = 3;
+const ATMOSPHERE_HEIGHT: f32 = 150.;
+const ATMOSPHERE_DENSITY: f32 = 0.005;
+const HEAT_GLOW: f32 = 20000.;
+const TRAJECTORY_COLOR: Color = Color { r: 180, g: 220, b: 255, a: 200 };
+const SOI_COLORS: [Color; 2] = [Color { r: 255, g: 210, b: 150, a: 200 },
+                                Color { r: 170, g: 255, b: 180, a: 200 }];
+const WINGMAN_SLOT: (f32, f32) = (60., 40.);
+const WINGMAN_MAX_SPEED: f32 = 300.;
+const FORMATION_SLOWING: f32 = 100.;
+const AVOID_LOOK_AHEAD: f32 = 1.5;
+const AVOID_CLEARANCE: f32 = 40.;
+const WINGMAN_TREE: &str = "media/wingman.tree";
+const GRAVITY_MODEL: &str = "media/gravity.model";
+const DERELICTS: [((f32, f32), (f32, f32)); 2] = [((0., -300.), (60., 0.)),
+                                                  ((0., 300.), (-60., 0.))];
+const DERELICT_SPIN: f32 = 20.;
+const TOW_LENGTH: f32 = 80.;
 
 #[derive(Clone, Copy)]
 enum TextureId {
@@ -32,6 +65,127 @@ impl ResourceId for TextureId {
     }
 }
 
+// What the wingman's behaviour tree can see and do.
+struct Pilot<'a> {
+    phys: &'a mut EntityPhysics,
+    limits: &'a SteeringLimits,
+    leader: &'a EntityPhysics,
+    planets: &'a PlanetManager,
+}
+
+impl<'a> Pilot<'a> {
+    const CONDITIONS: &'static [&'static str] = &["above", "near"];
+    const ACTIONS: &'static [&'static str] = &["climb", "form_up", "orbit"];
+
+    // Steer with ``force``, giving way to planets in the way.
+    fn fly(&mut self, force: Vector2f) -> Status {
+        let force = steering::prioritize(&[
+            steering::avoid_planets(self.phys, self.limits, self.planets,
+                                    AVOID_LOOK_AHEAD, AVOID_CLEARANCE),
+            force,
+        ], self.limits.max_force);
+        steering::steer(self.phys, self.limits, force);
+        Status::Running
+    }
+}
+
+impl<'a> Agent for Pilot<'a> {
+    // ``above key value`` compares a number on the blackboard, and
+    // ``near key distance`` measures the distance to a position on it.
+    fn condition(&self, name: &str, args: &[Arg], blackboard: &Blackboard) -> bool {
+        let key = args.first().and_then(Arg::name);
+        let value = args.get(1).and_then(Arg::number);
+
+        match (name, key, value) {
+            ("above", Some(key), Some(value)) => {
+                blackboard.number(key).is_some_and(|number| number > value)
+            },
+            ("near", Some(key), Some(distance)) => {
+                blackboard.vector(key)
+                    .is_some_and(|pos| (pos - self.phys.pos()).length() < distance)
+            },
+            _ => false,
+        }
+    }
+
+    // ``climb`` flees the nearest planet, ``form_up`` keeps formation on the
+    // leader, and ``orbit planet altitude seconds`` circles a planet for a while.
+    fn action(&mut self, name: &str, args: &[Arg], blackboard: &mut Blackboard, dt: f32)
+              -> Status {
+        match name {
+            "climb" => {
+                let pos = self.phys.pos();
+                let nearest = self.planets.planets().iter()
+                    .map(|planet| planet.phys().pos())
+                    .min_by(|a, b| (*a - pos).length_sq().total_cmp(&(*b - pos).length_sq()));
+
+                match nearest {
+                    Some(planet) => {
+                        let force = steering::flee(self.phys, self.limits, planet);
+                        self.fly(force)
+                    },
+                    None => Status::Failure,
+                }
+            },
+            "form_up" => {
+                let force = steering::keep_formation(self.phys, self.limits, self.leader,
+                                                     WINGMAN_SLOT, FORMATION_SLOWING);
+                self.fly(force)
+            },
+            "orbit" => {
+                let (planet, altitude, seconds) = match orbit_args(args) {
+                    Some((planet, altitude, seconds)) if planet < self.planets.planets().len() => {
+                        (planet, altitude, seconds)
+                    },
+                    _ => return Status::Failure,
+                };
+
+                // Remember how long we've been at it between ticks, separately
+                // for each orbit so that two running at once don't mix.
+                let timer = orbit_timer(args);
+                let elapsed = blackboard.number(&timer).unwrap_or(0.) + dt;
+                if elapsed >= seconds {
+                    blackboard.remove(&timer);
+                    return Status::Success;
+                }
+                blackboard.set(&timer, elapsed);
+
+                let radius = self.planets.planets()[planet].radius() + altitude;
+                let force = steering::orbit(self.phys, self.limits, self.planets, planet, radius,
+                                            OrbitDirection::Clockwise);
+                self.fly(force)
+            },
+            _ => Status::Failure,
+        }
+    }
+
+    fn abort(&mut self, name: &str, args: &[Arg], blackboard: &mut Blackboard) {
+        if name == "orbit" {
+            blackboard.remove(&orbit_timer(args));
+        }
+    }
+}
+
+// The planet, altitude and seconds of an ``orbit`` action.
+fn orbit_args(args: &[Arg]) -> Option<(usize, f32, f32)> {
+    match args {
+        [Arg::Number(planet), Arg::Number(altitude), Arg::Number(seconds)] => {
+            Some((*planet as usize, *altitude, *seconds))
+        },
+        _ => None,
+    }
+}
+
+// The blackboard key timing an ``orbit`` action, named after its arguments.
+fn orbit_timer(args: &[Arg]) -> String {
+    match orbit_args(args) {
+        Some((planet, altitude, seconds)) => {
+            format!("orbit_time {} {} {}", planet, altitude, seconds)
+        },
+        None => "orbit_time".to_owned(),
+    }
+}
+
 fn main() {
     let mut win = GameWindow::new(SIZE, "window");
 
@@ -72,7 +226,40 @@ fn main() {
 
     let mut s_entity = SpriteEntity::with_texture_phys(
         res.textures().get(TextureId::Spaceship0).unwrap(),
-        EntityPhysics::with_damping(1., 2., 0.02, 0.02));
+        EntityPhysics::with_damping(1., 2., 0.6, 0.6));
+    s_entity.phys_mut().set_integrator(Integrator::VelocityVerlet);
+    // The ship can fall fast enough to skip through the edge of a planet in one tick.
+    s_entity.phys_mut().set_bullet(true);
+
+    let ship_size = res.textures().get(TextureId::Spaceship0).unwrap().size();
+    s_entity.set_collider(Some(Collider::new(Shape::obb((ship_size.x as f32, ship_size.y as f32)))));
+
+    // The ship's nose points up. A pair of thrusters at the nose and tail pushing
+    // opposite ways turns it without pushing it sideways.
+    let half_length = ship_size.y as f32 / 2.;
+    let rcs_thrust = TORQUE / (2. * half_length);
+    let mut thrusters = Thrusters::new(FUEL);
+
+    let mut thruster = Thruster::new((0., half_length), (0., -1.), THRUST);
+    thruster.set_fuel_rate(ENGINE_FUEL_RATE);
+    let main_engine = thrusters.add(thruster);
+
+    thruster = Thruster::new((0., -half_length), (0., 1.), THRUST / 2.);
+    thruster.set_fuel_rate(ENGINE_FUEL_RATE / 2.);
+    let retro_engine = thrusters.add(thruster);
+
+    let mut rcs = |offset: (f32, f32), direction: (f32, f32), thrust: f32| {
+        let mut thruster = Thruster::new(offset, direction, thrust);
+        thruster.set_fuel_rate(RCS_FUEL_RATE);
+        thrusters.add(thruster)
+    };
+    let turn_right = [rcs((0., -half_length), (1., 0.), rcs_thrust),
+                      rcs((0., half_length), (-1., 0.), rcs_thrust)];
+    let turn_left = [rcs((0., -half_length), (-1., 0.), rcs_thrust),
+                     rcs((0., half_length), (1., 0.), rcs_thrust)];
+    let strafe_left = rcs((0., 0.), (-1., 0.), THRUST / 2.);
+    let strafe_right = rcs((0., 0.), (1., 0.), THRUST / 2.);
+    s_entity.set_thrusters(Some(thrusters));
 
     //let mut s_entity2 = SpriteEntity::with_texture_phys(
     //    res.textures().get(TextureId::Spaceship0).unwrap(),
@@ -81,31 +268,110 @@ fn main() {
 
     //let original_view = win.view().to_owned();
 
-    let planet = Planet::new(
+    let mut planet = Planet::new(
         res.textures().get(TextureId::Planet0).unwrap(),
         1000., (200., 20.));
+    planet.set_atmosphere(Some(Atmosphere::new(ATMOSPHERE_HEIGHT, ATMOSPHERE_DENSITY)));
 
     let planet2 = Planet::new(
         res.textures().get(TextureId::Planet0).unwrap(),
         1000., (-200., -20.));
 
-    let mut planet_manager = PlanetManager::new();
+    let mut planet_manager = PlanetManager::with_gravity(
+        GravityModel::from_file(GRAVITY_MODEL).unwrap());
     planet_manager.add_planet(planet);
     planet_manager.add_planet(planet2);
 
-    let mut last_tick: u64 = 0;
-    let begin = Instant::now();
+    // A wingman flies off the ship's right, steering itself with the same physics,
+    // and patrols on its own when left behind.
+    let mut wingman = SpriteEntity::with_texture_phys(
+        res.textures().get(TextureId::Spaceship0).unwrap(),
+        EntityPhysics::with_damping_pos(1., 2., 0.6, 0.6, WINGMAN_SLOT));
+    wingman.phys_mut().set_integrator(Integrator::VelocityVerlet);
+    wingman.phys_mut().set_bullet(true);
+    wingman.set_collider(s_entity.collider().cloned());
+    let wingman_limits = SteeringLimits::new(THRUST, WINGMAN_MAX_SPEED, TORQUE);
+    let mut wingman_tree = BehaviourTree::from_file(WINGMAN_TREE).unwrap();
+    wingman_tree.validate(Pilot::CONDITIONS, Pilot::ACTIONS).unwrap();
+
+    // Derelicts tumble between the planets, made of components and run by systems.
+    let mut registry = Registry::new();
+    for &(pos, vel) in &DERELICTS {
+        let mut derelict = SpriteEntity::with_texture_phys(
+            res.textures().get(TextureId::Spaceship0).unwrap(),
+            EntityPhysics::with_damping_pos(1., 2., 0., 0., pos));
+        derelict.phys_mut().set_vel(vel);
+        derelict.phys_mut().set_angular_vel(DERELICT_SPIN);
+        derelict.set_collider(s_entity.collider().cloned());
+        registry.spawn_bundle(derelict);
+    }
+
+    let mut schedule = Schedule::new();
+    schedule.add(systems::fire_thrusters);
+    schedule.add(systems::drag);
+    schedule.add(systems::physics);
+    schedule.add(systems::collide_planets);
+    schedule.add(systems::collide_entities);
+
+    // The ship tows a cargo pod on a rope from its tail.
+    let mut cargo = SpriteEntity::with_texture_phys(
+        res.textures().get(TextureId::Spaceship0).unwrap(),
+        EntityPhysics::with_damping_pos(1., 2., 0.6, 0.6, (0., 2. * half_length + TOW_LENGTH)));
+    cargo.set_collider(s_entity.collider().cloned());
+
+    let mut constraints = ConstraintSolver::new();
+    constraints.add(Joint::new(JointKind::Distance { length: TOW_LENGTH, rope: true },
+                               0, (0., half_length), 1, (0., -half_length))).unwrap();
+
+    let mut world = World::new();
+    let ship = world.spawn(s_entity);
+    let wingman = world.spawn(wingman);
+    let cargo = world.spawn(cargo);
+
+    let mut soi_tracker = SoiTracker::new();
+    let mut trajectory_line = TrajectoryLine::new(TRAJECTORY_COLOR);
+
+    let mut clock = GameClock::new();
     'game: loop {
-        let since = Instant::now().duration_since(begin);
-        let final_tick_frame = (since.as_secs() as f64 +
-                                since.subsec_nanos() as f64 * 1e-9 * TICKS_SEC as f64) as u64;
-        for _ in last_tick..final_tick_frame {
-            //println!("{} {}", last_tick, i);
-            planet_manager.affect_entity(&mut s_entity);
-            s_entity.update();
+        for _ in 0..clock.advance() {
+            let dt = clock.dt();
+            planet_manager.update(dt);
+            world.for_each_mut(|_, entity, _| {
+                planet_manager.drag_entity(entity, dt);
+            });
+
+            let leader = world.get(ship).unwrap().phys().clone();
+            let wingman_phys = world.get_mut(wingman).unwrap().phys_mut();
+            wingman_tree.blackboard_mut().set("leader", leader.pos());
+            wingman_tree.blackboard_mut().set("heat", wingman_phys.heat());
+            wingman_tree.tick(&mut Pilot {
+                phys: wingman_phys,
+                limits: &wingman_limits,
+                leader: &leader,
+                planets: &planet_manager,
+            }, dt);
+
+            world.update(dt, &planet_manager);
+            world.for_each_mut(|_, entity, _| {
+                planet_manager.collide_entity(entity);
+            });
+            world.collide();
+            world.solve_constraints(&mut constraints, &[ship, cargo], dt).unwrap();
+            schedule.run(&mut registry, &mut planet_manager, dt);
+
+            // Ships and derelicts live apart, so bump them into each other here.
+            world.for_each_mut(|_, entity, _| systems::collide_entity(&registry, entity));
+
+            // Tint the predicted path by the planet whose influence the ship is in.
+            let ship_pos = world.get(ship).unwrap().phys().pos();
+            for event in soi_tracker.update(&planet_manager, ship_pos) {
+                trajectory_line.set_color(match event {
+                    SoiEvent::Entered(planet) => SOI_COLORS[planet % SOI_COLORS.len()],
+                    SoiEvent::Exited(_) => TRAJECTORY_COLOR,
+                });
+            }
             //s_entity2.update();
         }
-        last_tick = final_tick_frame;
 
         bg.scroll(win.view());
 
@@ -118,43 +384,45 @@ fn main() {
 
         let keys = Inputs::current(&win).keys;
 
-        if keys.right {
-            s_entity.phys_mut().apply_force((0.05, 0.));
-            //s_entity2.phys_mut().apply_force((0.05, 0.));
-        }
+        let s_entity = world.get_as_mut::<SpriteEntity>(ship).unwrap();
 
-        if keys.left {
-            s_entity.phys_mut().apply_force((-0.05, 0.));
-            //s_entity2.phys_mut().apply_force((-0.05, 0.));
-        }
-
-        if keys.up {
-            s_entity.phys_mut().apply_force((0., -0.05));
-            //s_entity2.phys_mut().apply_force((0., -0.05));
-        }
-
-        if keys.down {
-            s_entity.phys_mut().apply_force((0., 0.05));
-            //s_entity2.phys_mut().apply_force((0., 0.05));
-        }
-
-        if keys.a {
-            s_entity.phys_mut().apply_torque(-0.01);
-            //s_entity2.phys_mut().apply_torque(-0.01);
-        }
+        // Up and down fire the main and retro engines, left and right turn,
+        // and A and D strafe.
+        if let Some(thrusters) = s_entity.thrusters_mut() {
+            let throttle = |pressed| if pressed { 1. } else { 0. };
 
-        if keys.d {
-            s_entity.phys_mut().apply_torque(0.01);
-            //s_entity2.phys_mut().apply_torque(0.01);
+            thrusters.set_throttle(main_engine, throttle(keys.up));
+            thrusters.set_throttle(retro_engine, throttle(keys.down));
+            for &i in &turn_left {
+                thrusters.set_throttle(i, throttle(keys.left));
+            }
+            for &i in &turn_right {
+                thrusters.set_throttle(i, throttle(keys.right));
+            }
+            thrusters.set_throttle(strafe_left, throttle(keys.a));
+            thrusters.set_throttle(strafe_right, throttle(keys.d));
         }
 
         if mouse::Button::Left.is_pressed() {
             let coords = win.map_pixel_to_coords_current_view(&win.mouse_position());
             println!("coords: {:?}", coords);
 
-            s_entity.phys_mut().apply_force_at((0.05, 0.), coords);
+            s_entity.phys_mut().apply_force_at((THRUST, 0.), coords);
         }
 
+        let trajectory = planet_manager.predict(s_entity.phys(), s_entity.collider(),
+                                                PREDICTION_TICKS, clock.dt(), PREDICTION_SAMPLE);
+        trajectory_line.set_trajectory(&trajectory);
+
+        // Glow red when hot from the atmosphere.
+        let cool = 255. * (1. - (s_entity.phys().heat() / HEAT_GLOW).min(1.));
+        s_entity.rc_sprite_mut().set_color(&Color::rgb(255, cool as u8, cool as u8));
+
+        let alpha = clock.alpha();
+        world.interpolate(alpha);
+        systems::sync_sprites(&registry, alpha);
+        planet_manager.interpolate(alpha);
+
         win.clear(&Color::BLACK);
         win.reset_view();
         win.draw_with_renderstates(&bg, RenderStates {
@@ -164,10 +432,12 @@ fn main() {
             },
             ..Default::default()
         });
-        win.center_view_on(&s_entity);
+        win.center_view_on_interpolated(world.get(ship).unwrap(), alpha);
         win.draw(&planet_manager);
+        win.draw(&trajectory_line);
         //win.draw(&tester);
-        win.draw(&s_entity);
+        win.draw(&*registry.storage::<RcSprite>());
+        win.draw(&world);
         //win.draw(&s_entity2);
         win.display();
     }